@@ -0,0 +1,202 @@
+use crate::maps::MapEntry;
+use crate::procfs::ProcessHandle;
+use crate::{LsofData, LsofFiletype, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How suspicious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Worth knowing, usually legitimate
+    Low,
+    /// Common in legitimate software such as JIT compilers, but worth a look
+    Medium,
+    /// Rarely legitimate
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+/// What was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FindingKind {
+    /// A mapping that is writable and executable at the same time
+    WritableExecutable {
+        /// Whether the mapping is anonymous rather than backed by a file
+        anonymous: bool,
+    },
+    /// Executable code mapped from a memfd, which never had a path on disk
+    ExecutableMemfd,
+    /// Executable code mapped from a file that was unlinked
+    ExecutableDeleted,
+    /// Executable code loaded from a world-writable directory
+    WorldWritableDirectory(PathBuf),
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindingKind::WritableExecutable { anonymous: true } => write!(f, "writable and executable anonymous mapping"),
+            FindingKind::WritableExecutable { anonymous: false } => write!(f, "writable and executable file mapping"),
+            FindingKind::ExecutableMemfd => write!(f, "executable memfd mapping"),
+            FindingKind::ExecutableDeleted => write!(f, "executable mapping of a deleted file"),
+            FindingKind::WorldWritableDirectory(dir) => {
+                write!(f, "executable loaded from world-writable {}", dir.display())
+            }
+        }
+    }
+}
+
+/// A suspicious mapping of a process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// How suspicious the finding is
+    pub severity: Severity,
+    /// What was found
+    pub kind: FindingKind,
+    /// The mapping the finding is about
+    pub mapping: MapEntry,
+}
+
+/// Check a single mapping for the findings that need nothing but the maps line
+fn classify(entry: &MapEntry) -> Vec<(Severity, FindingKind)> {
+    let mut findings = Vec::new();
+    if !entry.perms.execute {
+        return findings;
+    }
+
+    let anonymous = entry.inode == 0;
+    if entry.perms.write {
+        // JIT compilers use anonymous W+X memory; writable code in files is rarer
+        let severity = if anonymous { Severity::Medium } else { Severity::High };
+        findings.push((severity, FindingKind::WritableExecutable { anonymous }));
+    }
+    if is_memfd(entry) {
+        findings.push((Severity::High, FindingKind::ExecutableMemfd));
+    } else if entry.is_deleted() {
+        // Mostly libraries replaced by an upgrade, see `stale_ls`
+        findings.push((Severity::Low, FindingKind::ExecutableDeleted));
+    }
+    findings
+}
+
+/// Whether a mapping is backed by a memfd
+fn is_memfd(entry: &MapEntry) -> bool {
+    entry.path.as_deref().is_some_and(|path| path.starts_with("/memfd:"))
+}
+
+impl LsofData {
+    /// Whether `dir`, as seen from the process's root, is writable by everyone
+    fn is_world_writable(process: &ProcessHandle, dir: &Path) -> bool {
+        process
+            .stat(&format!("root{}", dir.display()))
+            .is_ok_and(|stat| stat.mode & libc::S_IFMT == libc::S_IFDIR && stat.mode & 0o002 != 0)
+    }
+
+    /// Check the mappings of every process for signs of injected or
+    /// tampered code
+    ///
+    /// Flags mappings that are writable and executable, executable mappings
+    /// of memfds and deleted files, and executables or libraries loaded from
+    /// world-writable directories such as `/tmp`. Findings are sorted by
+    /// severity, most severe first.
+    pub fn audit(&mut self) -> Result<Vec<Finding>> {
+        self.target_filetype = Some(LsofFiletype::Mem);
+
+        let findings = self.visit_processes(|data, pid, process, _| {
+            let entries = data.get_maps(process).map_err(|err| Self::pid_error(pid, err))?;
+            let mut found = Vec::new();
+            // World-writable checks per directory, since most files share a few
+            let mut directories: HashMap<&Path, bool> = HashMap::new();
+
+            for entry in &entries {
+                for (severity, kind) in classify(entry) {
+                    found.push((severity, kind, entry.clone()));
+                }
+                if !entry.perms.execute || entry.is_deleted() || is_memfd(entry) {
+                    continue;
+                }
+                let Some(dir) = entry.file_path().and_then(Path::parent) else {
+                    continue;
+                };
+                let writable = *directories.entry(dir).or_insert_with(|| Self::is_world_writable(process, dir));
+                if writable {
+                    let kind = FindingKind::WorldWritableDirectory(dir.to_path_buf());
+                    found.push((Severity::High, kind, entry.clone()));
+                }
+            }
+            if found.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let name = data.get_pid_info(process, pid)?.remove("Name");
+            Ok(found
+                .into_iter()
+                .map(|(severity, kind, mapping)| Finding {
+                    pid: pid.to_string(),
+                    name: name.clone(),
+                    severity,
+                    kind,
+                    mapping,
+                })
+                .collect())
+        })?;
+
+        let mut findings: Vec<Finding> = findings.into_iter().flatten().collect();
+        findings.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.pid.parse::<u64>().unwrap_or(u64::MAX).cmp(&b.pid.parse().unwrap_or(u64::MAX)))
+                .then(a.mapping.start.cmp(&b.mapping.start))
+        });
+        Ok(findings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+
+    #[test]
+    fn test_classify() {
+        let entries = maps::parse(
+            "\
+7f0000000000-7f0000001000 rwxp 00000000 00:00 0
+7f0000001000-7f0000002000 r-xs 00000000 00:01 1042 /memfd:payload (deleted)
+7f0000002000-7f0000003000 r-xp 00000000 08:01 77 /usr/lib/libold.so.1 (deleted)
+7f0000003000-7f0000004000 rwxp 00000000 08:01 78 /opt/app/plugin.so
+7f0000004000-7f0000005000 r-xp 00000000 08:01 79 /usr/lib/libc.so.6
+7f0000005000-7f0000006000 rw-p 00000000 08:01 80 /tmp/data (deleted)",
+        );
+        let kinds: Vec<_> = entries.iter().map(classify).collect();
+        assert_eq!(kinds[0], [(Severity::Medium, FindingKind::WritableExecutable { anonymous: true })]);
+        assert_eq!(kinds[1], [(Severity::High, FindingKind::ExecutableMemfd)]);
+        assert_eq!(kinds[2], [(Severity::Low, FindingKind::ExecutableDeleted)]);
+        assert_eq!(kinds[3], [(Severity::High, FindingKind::WritableExecutable { anonymous: false })]);
+        assert!(kinds[4].is_empty());
+        assert!(kinds[5].is_empty());
+        assert!(Severity::High > Severity::Medium);
+    }
+
+    #[test]
+    fn test_audit() {
+        let mut d = LsofData::new();
+        let findings = d.audit().unwrap();
+        assert!(findings.windows(2).all(|pair| pair[0].severity >= pair[1].severity));
+        assert!(d.scanned_at().is_some());
+    }
+}
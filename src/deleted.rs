@@ -0,0 +1,408 @@
+use crate::maps::DELETED_SUFFIX;
+use crate::mountinfo::{self, MountInfo};
+use crate::{Error, LsofData, Result};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// The filesystem a file lives on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filesystem {
+    /// Where the filesystem is mounted, as seen by the holder
+    pub mount_point: PathBuf,
+    /// Filesystem type, such as `ext4`
+    pub fs_type: String,
+    /// Filesystem source, such as `/dev/sda1`
+    pub source: String,
+}
+
+impl From<&MountInfo> for Filesystem {
+    fn from(mount: &MountInfo) -> Self {
+        Self {
+            mount_point: mount.mount_point.clone(),
+            fs_type: mount.fs_type.clone(),
+            source: mount.source.clone(),
+        }
+    }
+}
+
+/// A file that was unlinked but is still held open
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletedFile {
+    /// Process ID of the holder
+    pub pid: String,
+    /// Process name of the holder (if available)
+    pub name: Option<String>,
+    /// Descriptor the holder keeps the file open with
+    pub fd: u32,
+    /// Path the file had before it was unlinked
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    /// Space allocated on disk in bytes
+    pub allocated: u64,
+    /// Device holding the file, as `(major, minor)`
+    pub device: (u32, u32),
+    /// Inode number
+    pub inode: u64,
+    /// Filesystem the file lives on (if found in the holder's mount table)
+    pub filesystem: Option<Filesystem>,
+}
+
+/// Options for [`DeletedFile::truncate`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TruncateOptions {
+    /// Must be set for the file to actually be truncated
+    pub confirm: bool,
+    /// Run every check but leave the file untouched
+    pub dry_run: bool,
+    /// Truncate even while a process maps the file; it gets `SIGBUS` when
+    /// it touches the truncated pages
+    pub allow_mapped: bool,
+}
+
+impl DeletedFile {
+    /// Open the file through the holder's descriptor and check it is the one reported
+    ///
+    /// The checks run on the opened file itself, so a descriptor that was
+    /// closed and reused, or a pid that was recycled, since the scan is
+    /// caught instead of acted on.
+    fn open_checked(&self, options: &OpenOptions) -> Result<File> {
+        let file = options.open(format!("/proc/{}/fd/{}", self.pid, self.fd)).map_err(|err| {
+            match err.kind() {
+                io::ErrorKind::NotFound => Error::InodeMismatch { pid: self.pid.clone(), fd: self.fd },
+                _ => Error::Io(err),
+            }
+        })?;
+
+        let meta = file.metadata()?;
+        if mountinfo::dev_major_minor(meta.dev()) != self.device || meta.ino() != self.inode {
+            return Err(Error::InodeMismatch { pid: self.pid.clone(), fd: self.fd });
+        }
+        if meta.nlink() != 0 {
+            return Err(Error::NotDeleted { pid: self.pid.clone(), fd: self.fd });
+        }
+        Ok(file)
+    }
+
+    /// Get the processes mapping the file into memory
+    ///
+    /// Mappings are matched by device and inode, so they are found whether
+    /// or not the process still holds a descriptor. Processes whose maps
+    /// cannot be read are not checked.
+    fn mappers(&self) -> Result<Vec<String>> {
+        let mut scanner = LsofData::new();
+        let mappers = scanner.visit_processes(|data, pid, process, _| {
+            let maps = data.get_maps(process).map_err(|err| LsofData::pid_error(pid, err))?;
+            Ok(maps
+                .iter()
+                .any(|entry| entry.device == self.device && entry.inode == self.inode)
+                .then(|| pid.to_string()))
+        })?;
+        Ok(mappers.into_iter().flatten().collect())
+    }
+
+    /// Copy the contents of the file to `dest`, which must not exist yet
+    ///
+    /// Returns the number of bytes copied.
+    pub fn recover(&self, dest: impl AsRef<Path>) -> Result<u64> {
+        let mut source = self.open_checked(OpenOptions::new().read(true))?;
+        let mut target = OpenOptions::new().write(true).create_new(true).open(dest)?;
+        Ok(io::copy(&mut source, &mut target)?)
+    }
+
+    /// Truncate the file to zero length to free its space without
+    /// restarting the holder
+    ///
+    /// Nothing is changed unless `options.confirm` is set; with
+    /// `options.dry_run` every check runs but the file is left as it is.
+    /// A file mapped into memory by any process is refused unless
+    /// `options.allow_mapped` is set. A holder that keeps writing at its old
+    /// offset produces a sparse file, so the truncated range stays free.
+    /// Returns the number of bytes that are, or would be, freed.
+    pub fn truncate(&self, options: TruncateOptions) -> Result<u64> {
+        if !options.confirm && !options.dry_run {
+            return Err(Error::NotConfirmed);
+        }
+        let file = self.open_checked(OpenOptions::new().write(true))?;
+        if !options.allow_mapped {
+            let mappers = self.mappers()?;
+            if !mappers.is_empty() {
+                return Err(Error::FileMapped { pid: self.pid.clone(), fd: self.fd, mappers });
+            }
+        }
+        let freed = file.metadata()?.blocks() * 512;
+
+        if !options.dry_run {
+            file.set_len(0)?;
+        }
+        Ok(freed)
+    }
+}
+
+/// Space held by deleted files on one filesystem
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilesystemTotal {
+    /// Device of the filesystem, as `(major, minor)`
+    pub device: (u32, u32),
+    /// The filesystem (if found in a holder's mount table)
+    pub filesystem: Option<Filesystem>,
+    /// Number of distinct deleted files
+    pub files: usize,
+    /// Bytes that would be freed once every holder closes them
+    pub reclaimable: u64,
+}
+
+/// Deleted-but-open files, with totals per filesystem
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct DeletedReport {
+    /// One entry per holding descriptor
+    pub files: Vec<DeletedFile>,
+    /// Totals per filesystem, largest first
+    pub totals: Vec<FilesystemTotal>,
+}
+
+impl DeletedReport {
+    /// Sum the allocated space per filesystem, counting each inode once
+    fn compute_totals(&mut self) {
+        let mut seen: HashSet<((u32, u32), u64)> = HashSet::new();
+        let mut totals: HashMap<(u32, u32), FilesystemTotal> = HashMap::new();
+
+        for file in &self.files {
+            let total = totals.entry(file.device).or_insert_with(|| FilesystemTotal {
+                device: file.device,
+                filesystem: None,
+                files: 0,
+                reclaimable: 0,
+            });
+            if total.filesystem.is_none() {
+                total.filesystem = file.filesystem.clone();
+            }
+            if seen.insert((file.device, file.inode)) {
+                total.files += 1;
+                total.reclaimable += file.allocated;
+            }
+        }
+
+        self.totals = totals.into_values().collect();
+        self.totals.sort_by(|a, b| b.reclaimable.cmp(&a.reclaimable).then(a.device.cmp(&b.device)));
+    }
+}
+
+impl LsofData {
+    /// Get files that were unlinked but are still held open
+    ///
+    /// Each file is measured with fstat through `/proc/<pid>/fd/<fd>` in the
+    /// same pass that lists it, through the same process handle. memfd
+    /// descriptors are left out since they never had a path on disk.
+    pub fn deleted_ls(&mut self) -> Result<DeletedReport> {
+        self.target_filetype = None;
+        self.target_filename = String::new();
+
+        // Mount tables per mount namespace of the holders
+        let mut mount_tables: HashMap<PathBuf, Vec<MountInfo>> = HashMap::new();
+        let files = self.visit_processes(|data, pid, process, diagnostics| {
+            let info = data.scan_process(pid, process, diagnostics)?;
+            let deleted: Vec<_> = info
+                .fds
+                .iter()
+                .filter(|entry| entry.link.ends_with(DELETED_SUFFIX) && !entry.link.starts_with("/memfd:"))
+                .collect();
+            if deleted.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let mounts = match process.read_link("ns/mnt") {
+                Ok(ns) => match mount_tables.entry(ns) {
+                    Entry::Occupied(entry) => Some(&*entry.into_mut()),
+                    Entry::Vacant(entry) => match process.read_to_string("mountinfo") {
+                        Ok(content) => Some(&*entry.insert(mountinfo::parse(&content))),
+                        Err(err) => {
+                            diagnostics.unreadable(format!("/proc/{}/mountinfo", pid), &err);
+                            None
+                        }
+                    },
+                },
+                Err(err) => {
+                    diagnostics.unreadable(format!("/proc/{}/ns/mnt", pid), &err);
+                    None
+                }
+            };
+
+            let mut files = Vec::new();
+            for entry in deleted {
+                let fd_name = format!("fd/{}", entry.fd);
+                let stat = match process.stat(&fd_name) {
+                    Ok(stat) => stat,
+                    // Closed since it was listed
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    Err(err) if Self::is_gone(&err) => return Err(Error::ProcessVanished { pid: pid.to_string() }),
+                    Err(err) => {
+                        diagnostics.unreadable(format!("/proc/{}/{}", pid, fd_name), &err);
+                        continue;
+                    }
+                };
+                // The descriptor may have been closed and reused since it was listed
+                let relinked = process.read_link(&fd_name).map(|link| link.to_string_lossy() != entry.link);
+                // A linked file may itself be named "... (deleted)"
+                if !stat.is_file() || stat.nlink != 0 || relinked.unwrap_or(true) {
+                    continue;
+                }
+
+                let device = mountinfo::dev_major_minor(stat.dev);
+                files.push(DeletedFile {
+                    pid: info.pid.clone(),
+                    name: info.name.clone(),
+                    fd: entry.fd,
+                    path: PathBuf::from(entry.link.trim_end_matches(DELETED_SUFFIX)),
+                    size: stat.size,
+                    allocated: stat.blocks * 512,
+                    device,
+                    inode: stat.ino,
+                    filesystem: mounts
+                        .and_then(|mounts| mountinfo::find_device(mounts, device.0, device.1))
+                        .map(Filesystem::from),
+                });
+            }
+            Ok(files)
+        })?;
+
+        let mut report = DeletedReport { files: files.into_iter().flatten().collect(), totals: Vec::new() };
+        report.files.sort_by(|a, b| b.allocated.cmp(&a.allocated).then(a.fd.cmp(&b.fd)));
+        report.compute_totals();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn test_deleted_ls() {
+        let path = std::env::temp_dir().join(format!("minilsof-deleted-{}", std::process::id()));
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(&[1u8; 8192]).unwrap();
+        file.sync_all().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut d = LsofData::new();
+        let report = d.deleted_ls().unwrap();
+        let me = std::process::id().to_string();
+        let found = report
+            .files
+            .iter()
+            .find(|f| f.pid == me && f.path == path)
+            .expect("deleted file held by the test was not reported");
+        assert_eq!(found.size, 8192);
+
+        let total = report.totals.iter().find(|t| t.device == found.device).unwrap();
+        assert!(total.files >= 1);
+        assert!(total.reclaimable >= found.allocated);
+        drop(file);
+    }
+
+    #[test]
+    fn test_deleted_ls_linked_lookalike() {
+        let path = std::env::temp_dir().join(format!("minilsof-lookalike-{}{}", std::process::id(), DELETED_SUFFIX));
+        let file = fs::File::create(&path).unwrap();
+
+        let mut d = LsofData::new();
+        let report = d.deleted_ls().unwrap();
+        let me = std::process::id().to_string();
+        assert!(report.files.iter().all(|f| f.pid != me || f.inode != file.metadata().unwrap().ino()));
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recover_and_truncate() {
+        let path = std::env::temp_dir().join(format!("minilsof-truncate-{}", std::process::id()));
+        let dest = std::env::temp_dir().join(format!("minilsof-recovered-{}", std::process::id()));
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(b"still needed").unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut d = LsofData::new();
+        let me = std::process::id().to_string();
+        let report = d.deleted_ls().unwrap();
+        let deleted = report.files.iter().find(|f| f.pid == me && f.path == path).unwrap();
+
+        assert_eq!(deleted.recover(&dest).unwrap(), 12);
+        assert_eq!(fs::read(&dest).unwrap(), b"still needed");
+        fs::remove_file(&dest).unwrap();
+
+        assert!(matches!(deleted.truncate(TruncateOptions::default()), Err(Error::NotConfirmed)));
+        let dry_run = TruncateOptions { dry_run: true, ..Default::default() };
+        deleted.truncate(dry_run).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 12);
+
+        let wrong = DeletedFile { inode: deleted.inode + 1, ..deleted.clone() };
+        assert!(matches!(wrong.truncate(dry_run), Err(Error::InodeMismatch { .. })));
+
+        let confirm = TruncateOptions { confirm: true, ..Default::default() };
+        deleted.truncate(confirm).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_truncate_mapped() {
+        let path = std::env::temp_dir().join(format!("minilsof-mapped-{}", std::process::id()));
+        let mut file = OpenOptions::new().read(true).write(true).create_new(true).open(&path).unwrap();
+        file.write_all(&[1u8; 4096]).unwrap();
+        // SAFETY: a fresh shared read-only mapping of an open file, unmapped below
+        let map = unsafe { libc::mmap(std::ptr::null_mut(), 4096, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0) };
+        assert_ne!(map, libc::MAP_FAILED);
+        fs::remove_file(&path).unwrap();
+
+        let mut d = LsofData::new();
+        let me = std::process::id().to_string();
+        let report = d.deleted_ls().unwrap();
+        let deleted = report.files.iter().find(|f| f.pid == me && f.path == path).unwrap();
+
+        let confirm = TruncateOptions { confirm: true, ..Default::default() };
+        match deleted.truncate(confirm) {
+            Err(Error::FileMapped { mappers, .. }) => assert!(mappers.contains(&me)),
+            result => panic!("mapped file was not refused: {:?}", result),
+        }
+        let dry_run = TruncateOptions { dry_run: true, ..Default::default() };
+        assert!(matches!(deleted.truncate(dry_run), Err(Error::FileMapped { .. })));
+        let allowed = TruncateOptions { allow_mapped: true, ..dry_run };
+        assert_eq!(deleted.truncate(allowed).unwrap(), deleted.allocated);
+        assert_eq!(file.metadata().unwrap().len(), 4096);
+
+        // SAFETY: `map` is the 4096-byte mapping created above
+        assert_eq!(unsafe { libc::munmap(map, 4096) }, 0);
+        deleted.truncate(confirm).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_totals_count_inode_once() {
+        let file = |pid: &str, fd: u32| DeletedFile {
+            pid: pid.to_string(),
+            name: None,
+            fd,
+            path: PathBuf::from("/var/log/big.log"),
+            size: 100,
+            allocated: 4096,
+            device: (8, 1),
+            inode: 42,
+            filesystem: None,
+        };
+        let mut report = DeletedReport {
+            files: vec![file("1", 3), file("2", 7)],
+            totals: Vec::new(),
+        };
+        report.compute_totals();
+        assert_eq!(report.totals.len(), 1);
+        assert_eq!(report.totals[0].files, 1);
+        assert_eq!(report.totals[0].reclaimable, 4096);
+    }
+}
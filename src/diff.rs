@@ -0,0 +1,164 @@
+use crate::{FdEntry, Fdinfo};
+use std::collections::{HashMap, HashSet};
+
+/// Difference between two scans of the same host
+///
+/// Both sides are pid maps as returned by `file_ls`. A pid present on both
+/// sides with different start times was reused, and is reported as one
+/// process exiting and another starting.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ScanDiff {
+    /// Processes only present in the later scan
+    pub started: Vec<Fdinfo>,
+    /// Processes only present in the earlier scan
+    pub exited: Vec<Fdinfo>,
+    /// Processes present in both scans whose descriptors changed
+    pub changed: Vec<ProcessDiff>,
+}
+
+/// Descriptor changes of a single process between two scans
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ProcessDiff {
+    /// Process ID
+    pub pid: String,
+    /// Process name in the later scan (if available)
+    pub name: Option<String>,
+    /// Descriptors only present in the later scan
+    pub opened: Vec<FdEntry>,
+    /// Descriptors only present in the earlier scan
+    pub closed: Vec<FdEntry>,
+    /// Number of descriptors in the earlier scan
+    pub fd_count_before: usize,
+    /// Number of descriptors in the later scan
+    pub fd_count_after: usize,
+}
+
+impl ProcessDiff {
+    /// Change in the number of open descriptors
+    pub fn fd_delta(&self) -> i64 {
+        self.fd_count_after as i64 - self.fd_count_before as i64
+    }
+}
+
+impl ScanDiff {
+    /// Whether the two scans were identical
+    pub fn is_empty(&self) -> bool {
+        self.started.is_empty() && self.exited.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Sort key that orders pids numerically
+fn pid_key(pid: &str) -> u64 {
+    pid.parse().unwrap_or(u64::MAX)
+}
+
+/// Whether two records of the same pid describe the same process instance
+fn same_process(before: &Fdinfo, after: &Fdinfo) -> bool {
+    match (before.start_time, after.start_time) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+/// Compare the descriptors of one process, keyed by fd number plus target
+fn diff_process<'a>(before: &'a Fdinfo, after: &'a Fdinfo) -> Option<ProcessDiff> {
+    // Offsets and flags change without the file being reopened, so they are not compared
+    let key = |e: &'a FdEntry| (e.fd, e.link.as_str());
+    let old: HashSet<(u32, &str)> = before.fds.iter().map(key).collect();
+    let new: HashSet<(u32, &str)> = after.fds.iter().map(key).collect();
+
+    let opened: Vec<FdEntry> = after.fds.iter().filter(|e| !old.contains(&key(e))).cloned().collect();
+    let closed: Vec<FdEntry> = before.fds.iter().filter(|e| !new.contains(&key(e))).cloned().collect();
+    if opened.is_empty() && closed.is_empty() {
+        return None;
+    }
+
+    Some(ProcessDiff {
+        pid: after.pid.clone(),
+        name: after.name.clone(),
+        opened,
+        closed,
+        fd_count_before: before.fds.len(),
+        fd_count_after: after.fds.len(),
+    })
+}
+
+/// Compare two scans
+pub fn diff(before: &HashMap<String, Fdinfo>, after: &HashMap<String, Fdinfo>) -> ScanDiff {
+    let mut result = ScanDiff::default();
+
+    for (pid, old) in before {
+        match after.get(pid) {
+            Some(new) if same_process(old, new) => {
+                result.changed.extend(diff_process(old, new));
+            }
+            Some(new) => {
+                result.exited.push(old.clone());
+                result.started.push(new.clone());
+            }
+            None => result.exited.push(old.clone()),
+        }
+    }
+    for (pid, new) in after {
+        if !before.contains_key(pid) {
+            result.started.push(new.clone());
+        }
+    }
+
+    result.started.sort_by_key(|info| pid_key(&info.pid));
+    result.exited.sort_by_key(|info| pid_key(&info.pid));
+    result.changed.sort_by_key(|process| pid_key(&process.pid));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: &str, start_time: u64, fds: &[(u32, &str)]) -> Fdinfo {
+        Fdinfo {
+            pid: pid.to_string(),
+            start_time: Some(start_time),
+            fds: fds
+                .iter()
+                .map(|(fd, link)| FdEntry { fd: *fd, link: link.to_string(), ..Default::default() })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn scan(processes: Vec<Fdinfo>) -> HashMap<String, Fdinfo> {
+        processes.into_iter().map(|info| (info.pid.clone(), info)).collect()
+    }
+
+    #[test]
+    fn test_diff_descriptors() {
+        let before = scan(vec![
+            process("1", 10, &[(0, "/dev/null"), (3, "/var/log/a")]),
+            process("2", 20, &[(0, "/dev/null")]),
+            process("3", 30, &[]),
+        ]);
+        let after = scan(vec![
+            process("1", 10, &[(0, "/dev/null"), (3, "/var/log/b"), (4, "socket:[7]")]),
+            process("3", 31, &[]),
+            process("4", 40, &[]),
+        ]);
+
+        let result = diff(&before, &after);
+        let pids = |list: &[Fdinfo]| list.iter().map(|i| i.pid.clone()).collect::<Vec<_>>();
+        assert_eq!(pids(&result.started), ["3", "4"]);
+        assert_eq!(pids(&result.exited), ["2", "3"]);
+
+        assert_eq!(result.changed.len(), 1);
+        let changed = &result.changed[0];
+        assert_eq!(changed.opened.len(), 2);
+        assert_eq!(changed.closed, [FdEntry { fd: 3, link: "/var/log/a".to_string(), ..Default::default() }]);
+        assert_eq!(changed.fd_delta(), 1);
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let before = scan(vec![process("1", 10, &[(0, "/dev/null")])]);
+        assert!(diff(&before, &before.clone()).is_empty());
+    }
+}
@@ -0,0 +1,675 @@
+use crate::mountinfo::{self, MountInfo};
+use crate::procfs::owned_fd;
+use crate::{FdEntry, LsofData, Result};
+use std::ffi::CString;
+use std::fs;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Clock a timerfd counts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Clock {
+    /// `CLOCK_REALTIME`
+    Realtime,
+    /// `CLOCK_MONOTONIC`
+    Monotonic,
+    /// `CLOCK_BOOTTIME`
+    Boottime,
+    /// `CLOCK_REALTIME_ALARM`
+    RealtimeAlarm,
+    /// `CLOCK_BOOTTIME_ALARM`
+    BoottimeAlarm,
+    /// Any other clock, by ID
+    Other(i32),
+}
+
+impl Clock {
+    /// Convert a `clockid_t`
+    fn from_raw(raw: i32) -> Self {
+        match raw {
+            libc::CLOCK_REALTIME => Clock::Realtime,
+            libc::CLOCK_MONOTONIC => Clock::Monotonic,
+            libc::CLOCK_BOOTTIME => Clock::Boottime,
+            libc::CLOCK_REALTIME_ALARM => Clock::RealtimeAlarm,
+            libc::CLOCK_BOOTTIME_ALARM => Clock::BoottimeAlarm,
+            other => Clock::Other(other),
+        }
+    }
+}
+
+/// A descriptor watched by an epoll instance
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EpollTarget {
+    /// Descriptor number, in the process that added it
+    pub fd: u32,
+    /// Events watched for, as `EPOLLIN` and friends
+    pub events: u32,
+    /// User data registered with the descriptor
+    pub data: u64,
+}
+
+/// State of a timerfd
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TimerFd {
+    /// Clock the timer counts on
+    pub clock: Clock,
+    /// Expirations not yet read
+    pub ticks: u64,
+    /// Flags the timer was last set with, as `TFD_TIMER_ABSTIME`
+    pub settime_flags: u32,
+    /// Time until the next expiry, `None` if the timer is disarmed
+    pub next_expiry: Option<Duration>,
+    /// Interval between expiries, zero for a one-shot timer
+    pub interval: Duration,
+}
+
+/// Opaque kernel handle of a watched file, as `open_by_handle_at` takes it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct FileHandle {
+    pub(crate) handle_type: i32,
+    pub(crate) bytes: Vec<u8>,
+}
+
+/// A file or directory watched by an inotify instance
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InotifyWatch {
+    /// Watch descriptor
+    pub wd: i32,
+    /// Inode of the watched file
+    pub inode: u64,
+    /// Device of the watched file, as `(major, minor)`
+    pub device: (u32, u32),
+    /// Events watched for, as `IN_MODIFY` and friends
+    pub mask: u32,
+    /// Events ignored
+    pub ignored_mask: u32,
+    /// Path of the watched file, if resolved by [`resolve_paths`]
+    pub path: Option<PathBuf>,
+    /// Handle used to resolve the path
+    pub(crate) handle: Option<FileHandle>,
+}
+
+/// What a fanotify mark is placed on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FanotifyTarget {
+    /// A single file or directory
+    Inode {
+        /// Inode of the file
+        inode: u64,
+        /// Device of the file, as `(major, minor)`
+        device: (u32, u32),
+    },
+    /// Every file under a mount, by mount ID
+    Mount(u32),
+    /// Every file of a filesystem, by device as `(major, minor)`
+    Filesystem((u32, u32)),
+}
+
+/// A mark of a fanotify instance
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FanotifyMark {
+    /// What the mark is placed on
+    pub target: FanotifyTarget,
+    /// Mark flags, as `FAN_MARK_*`
+    pub flags: u32,
+    /// Events watched for, as `FAN_OPEN` and friends
+    pub mask: u32,
+    /// Events ignored
+    pub ignored_mask: u32,
+    /// Path of the marked file for inode marks, if resolved by [`resolve_paths`]
+    pub path: Option<PathBuf>,
+    /// Handle used to resolve the path
+    pub(crate) handle: Option<FileHandle>,
+}
+
+/// A BPF map
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BpfMap {
+    /// Map type, as `BPF_MAP_TYPE_*`
+    pub map_type: u32,
+    /// System-wide ID of the map
+    pub map_id: u32,
+    /// Size of a key in bytes
+    pub key_size: u32,
+    /// Size of a value in bytes
+    pub value_size: u32,
+    /// Maximum number of entries
+    pub max_entries: u32,
+}
+
+/// A loaded BPF program
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BpfProg {
+    /// Program type, as `BPF_PROG_TYPE_*`
+    pub prog_type: u32,
+    /// System-wide ID of the program
+    pub prog_id: u32,
+    /// Hash of the program's instructions, in hex, as `bpftool` shows it
+    pub tag: String,
+    /// Whether the program was compiled to native code
+    pub jited: bool,
+}
+
+/// An io_uring instance
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IoUring {
+    /// Thread ID of the kernel thread polling the submission queue, if any
+    pub sq_thread: Option<u32>,
+    /// CPU the polling thread is bound to, if any
+    pub sq_thread_cpu: Option<u32>,
+    /// Registered files, by slot
+    pub files: Vec<(u32, String)>,
+}
+
+/// What the fdinfo of an anonymous inode says, by kind
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FdDetail {
+    /// An eventfd and its counter
+    EventFd {
+        /// Current value of the counter
+        count: u64,
+    },
+    /// An epoll instance and the descriptors it watches
+    Epoll(Vec<EpollTarget>),
+    /// A timerfd
+    TimerFd(TimerFd),
+    /// A signalfd
+    SignalFd {
+        /// Signals accepted, with bit `n - 1` for signal `n`
+        sigmask: u64,
+    },
+    /// An inotify instance and its watches
+    Inotify(Vec<InotifyWatch>),
+    /// A fanotify instance and its marks
+    Fanotify(Vec<FanotifyMark>),
+    /// A BPF map
+    BpfMap(BpfMap),
+    /// A BPF program
+    BpfProg(BpfProg),
+    /// An io_uring instance
+    IoUring(IoUring),
+    /// A pidfd
+    PidFd {
+        /// Process the pidfd refers to, `None` if it exited or is outside
+        /// the caller's PID namespace
+        pid: Option<u32>,
+    },
+}
+
+impl FdDetail {
+    /// Whether this is a BPF map or program
+    pub fn is_bpf(&self) -> bool {
+        matches!(self, FdDetail::BpfMap(_) | FdDetail::BpfProg(_))
+    }
+}
+
+/// Split a device number in the kernel's internal encoding, as printed in
+/// `sdev:` fields, into major and minor numbers
+pub(crate) fn kernel_dev(dev: u32) -> (u32, u32) {
+    (dev >> 20, dev & 0xfffff)
+}
+
+/// Get the value after `key` in a line of `key:value` fields
+pub(crate) fn field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.split_whitespace().find_map(|word| word.strip_prefix(key)?.strip_prefix(':'))
+}
+
+/// Get a hex value after `key` in a line of `key:value` fields
+pub(crate) fn hex_field(line: &str, key: &str) -> Option<u64> {
+    u64::from_str_radix(field(line, key)?, 16).ok()
+}
+
+/// Decode the value of a `(seconds, nanoseconds)` pair
+fn parse_timespec(value: &str) -> Option<Duration> {
+    let (secs, nanos) = value.trim().strip_prefix('(')?.strip_suffix(')')?.split_once(',')?;
+    Some(Duration::new(secs.trim().parse().ok()?, nanos.trim().parse().ok()?))
+}
+
+/// Decode the `f_handle` of a watch line
+pub(crate) fn parse_handle(line: &str) -> Option<FileHandle> {
+    let hex = field(line, "f_handle")?;
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(FileHandle { handle_type: hex_field(line, "fhandle-type")? as i32, bytes })
+}
+
+/// Parse one `inotify` line of an fdinfo
+fn parse_watch(line: &str) -> Option<InotifyWatch> {
+    let line = line.strip_prefix("inotify ")?;
+    Some(InotifyWatch {
+        wd: hex_field(line, "wd")? as i32,
+        inode: hex_field(line, "ino")?,
+        device: kernel_dev(hex_field(line, "sdev")? as u32),
+        mask: hex_field(line, "mask")? as u32,
+        ignored_mask: hex_field(line, "ignored_mask").unwrap_or(0) as u32,
+        path: None,
+        handle: parse_handle(line),
+    })
+}
+
+/// Parse one `fanotify` mark line of an fdinfo, skipping the header line
+fn parse_mark(line: &str) -> Option<FanotifyMark> {
+    let line = line.strip_prefix("fanotify ")?;
+    let target = if let Some(inode) = hex_field(line, "ino") {
+        FanotifyTarget::Inode { inode, device: kernel_dev(hex_field(line, "sdev")? as u32) }
+    } else if let Some(mount) = hex_field(line, "mnt_id") {
+        FanotifyTarget::Mount(mount as u32)
+    } else {
+        FanotifyTarget::Filesystem(kernel_dev(hex_field(line, "sdev")? as u32))
+    };
+    Some(FanotifyMark {
+        target,
+        flags: hex_field(line, "mflags")? as u32,
+        mask: hex_field(line, "mask")? as u32,
+        ignored_mask: hex_field(line, "ignored_mask").unwrap_or(0) as u32,
+        path: None,
+        handle: parse_handle(line),
+    })
+}
+
+/// Parse the registered files of an io_uring fdinfo, listed as
+/// `<slot>: <path>` after `UserFiles:`
+fn parse_uring_files(content: &str) -> Vec<(u32, String)> {
+    content
+        .lines()
+        .skip_while(|line| !line.starts_with("UserFiles:"))
+        .skip(1)
+        .map_while(|line| {
+            let (slot, path) = line.split_once(": ")?;
+            Some((slot.trim().parse().ok()?, mountinfo::unescape(path)))
+        })
+        .collect()
+}
+
+/// Decode the fdinfo of a descriptor, given the target of its link
+///
+/// Returns `None` for descriptors that are not an anonymous inode of a
+/// kind decoded here.
+pub fn parse(link: &str, content: &str) -> Option<FdDetail> {
+    let kind = link.strip_prefix("anon_inode:")?;
+    let kind = kind.strip_prefix('[').and_then(|k| k.strip_suffix(']')).unwrap_or(kind);
+    let value = |key: &str| content.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(':')).map(str::trim);
+
+    match kind {
+        "eventfd" => Some(FdDetail::EventFd { count: u64::from_str_radix(value("eventfd-count")?, 16).ok()? }),
+        "eventpoll" => Some(FdDetail::Epoll(
+            content
+                .lines()
+                .filter(|line| line.starts_with("tfd:"))
+                .filter_map(|line| {
+                    // Values are padded, so "tfd:        5" needs the next word
+                    let mut words = line.split_whitespace();
+                    let mut next = |key: &str| words.find(|word| *word == key).and_then(|_| words.next());
+                    Some(EpollTarget {
+                        fd: next("tfd:")?.parse().ok()?,
+                        events: u32::from_str_radix(next("events:")?, 16).ok()?,
+                        data: u64::from_str_radix(next("data:")?, 16).ok()?,
+                    })
+                })
+                .collect(),
+        )),
+        "timerfd" => {
+            let next_expiry = parse_timespec(value("it_value")?)?;
+            Some(FdDetail::TimerFd(TimerFd {
+                clock: Clock::from_raw(value("clockid")?.parse().ok()?),
+                ticks: value("ticks")?.parse().ok()?,
+                // Printed in octal
+                settime_flags: u32::from_str_radix(value("settime flags")?, 8).ok()?,
+                next_expiry: (!next_expiry.is_zero()).then_some(next_expiry),
+                interval: parse_timespec(value("it_interval")?)?,
+            }))
+        }
+        "signalfd" => Some(FdDetail::SignalFd { sigmask: u64::from_str_radix(value("sigmask")?, 16).ok()? }),
+        "inotify" => Some(FdDetail::Inotify(content.lines().filter_map(parse_watch).collect())),
+        "fanotify" => Some(FdDetail::Fanotify(content.lines().filter_map(parse_mark).collect())),
+        "bpf-map" => Some(FdDetail::BpfMap(BpfMap {
+            map_type: value("map_type")?.parse().ok()?,
+            map_id: value("map_id")?.parse().ok()?,
+            key_size: value("key_size")?.parse().ok()?,
+            value_size: value("value_size")?.parse().ok()?,
+            max_entries: value("max_entries")?.parse().ok()?,
+        })),
+        "bpf-prog" => Some(FdDetail::BpfProg(BpfProg {
+            prog_type: value("prog_type")?.parse().ok()?,
+            prog_id: value("prog_id")?.parse().ok()?,
+            tag: value("prog_tag")?.to_string(),
+            jited: value("prog_jited") == Some("1"),
+        })),
+        "io_uring" => {
+            // -1 when there is no polling thread
+            let thread = |key| value(key).and_then(|v| v.parse().ok());
+            Some(FdDetail::IoUring(IoUring {
+                sq_thread: thread("SqThread"),
+                sq_thread_cpu: thread("SqThreadCpu"),
+                files: parse_uring_files(content),
+            }))
+        }
+        // -1 once the process exited, 0 outside our PID namespace
+        "pidfd" => Some(FdDetail::PidFd { pid: value("Pid")?.parse().ok().filter(|&pid| pid > 0) }),
+        _ => None,
+    }
+}
+
+/// Resolve a file handle on device `device` to a path
+///
+/// Needs `CAP_DAC_READ_SEARCH` and the filesystem mounted in the caller's
+/// mount namespace.
+pub(crate) fn resolve_handle(handle: &FileHandle, device: (u32, u32), mounts: &[MountInfo]) -> Option<PathBuf> {
+    let mount = mountinfo::find_device(mounts, device.0, device.1)?;
+    let mount_point = CString::new(mount.mount_point.as_os_str().as_bytes()).ok()?;
+    // open_by_handle_at does not take an O_PATH descriptor for the mount
+    // SAFETY: `mount_point` is a valid C string
+    let mount_fd = owned_fd(unsafe { libc::open(mount_point.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC) }).ok()?;
+
+    // struct file_handle: handle_bytes, handle_type, then the handle itself
+    let mut raw = Vec::with_capacity(8 + handle.bytes.len());
+    raw.extend_from_slice(&(handle.bytes.len() as u32).to_ne_bytes());
+    raw.extend_from_slice(&handle.handle_type.to_ne_bytes());
+    raw.extend_from_slice(&handle.bytes);
+    // SAFETY: `raw` holds a complete file_handle and outlives the call
+    let fd = owned_fd(unsafe {
+        libc::syscall(
+            libc::SYS_open_by_handle_at,
+            mount_fd.as_raw_fd(),
+            raw.as_mut_ptr(),
+            libc::O_PATH | libc::O_CLOEXEC,
+        ) as libc::c_int
+    })
+    .ok()?;
+    fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd())).ok()
+}
+
+/// Fill in the paths of the inotify watches and fanotify inode marks among `fds`
+///
+/// Scans leave the paths out, since resolving reads the mount table, opens
+/// mount points, which can trigger automounts, and calls
+/// `open_by_handle_at` once per watch. [`LsofData::process_ls`] resolves
+/// them; call this for descriptors from other queries. The mount table is
+/// only read if there is a watch to resolve.
+pub fn resolve_paths(fds: &mut [FdEntry]) {
+    let mut mounts: Option<Vec<MountInfo>> = None;
+    let mut resolve = |handle: &Option<FileHandle>, device| {
+        let mounts = mounts.get_or_insert_with(|| mountinfo::read("self").unwrap_or_default());
+        resolve_handle(handle.as_ref()?, device, mounts)
+    };
+    for entry in fds {
+        match &mut entry.detail {
+            Some(FdDetail::Inotify(watches)) => {
+                for watch in watches.iter_mut().filter(|watch| watch.handle.is_some()) {
+                    watch.path = resolve(&watch.handle, watch.device);
+                }
+            }
+            Some(FdDetail::Fanotify(marks)) => {
+                for mark in marks.iter_mut().filter(|mark| mark.handle.is_some()) {
+                    if let FanotifyTarget::Inode { device, .. } = mark.target {
+                        mark.path = resolve(&mark.handle, device);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A descriptor with decoded fdinfo, and the process holding it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetailedFd {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// Descriptor number
+    pub fd: u32,
+    /// Decoded fdinfo
+    pub detail: FdDetail,
+}
+
+/// Get the name of a process, if it is still running
+fn process_name(pid: u32) -> Option<String> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status.lines().find_map(|line| Some(line.strip_prefix("Name:")?.trim().to_string()))
+}
+
+/// A process tracked through a pidfd
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PidFdTarget {
+    /// Descriptor of the pidfd in the tracking process
+    pub fd: u32,
+    /// Process the pidfd refers to, `None` if it exited or is outside the
+    /// caller's PID namespace
+    pub pid: Option<u32>,
+    /// Name of that process (if available)
+    pub name: Option<String>,
+}
+
+impl LsofData {
+    /// Get every descriptor whose decoded fdinfo `keep` accepts
+    pub(crate) fn detail_ls(&mut self, keep: impl Fn(&FdDetail) -> bool) -> Result<Vec<DetailedFd>> {
+        self.target_filetype = None;
+        self.target_filename = String::new();
+
+        let found = self.visit_processes(|data, pid, process, diagnostics| {
+            let info = data.scan_process(pid, process, diagnostics)?;
+            Ok(info
+                .fds
+                .into_iter()
+                .filter_map(|entry| {
+                    let detail = entry.detail.filter(|detail| keep(detail))?;
+                    Some(DetailedFd { pid: info.pid.clone(), name: info.name.clone(), fd: entry.fd, detail })
+                })
+                .collect::<Vec<_>>())
+        })?;
+        Ok(found.into_iter().flatten().collect())
+    }
+
+    /// Get every descriptor of a BPF map or program, with its type and ID
+    pub fn bpf_ls(&mut self) -> Result<Vec<DetailedFd>> {
+        self.detail_ls(FdDetail::is_bpf)
+    }
+
+    /// Get every descriptor of an io_uring instance, with its polling
+    /// thread and registered files
+    pub fn io_uring_ls(&mut self) -> Result<Vec<DetailedFd>> {
+        self.detail_ls(|detail| matches!(detail, FdDetail::IoUring(_)))
+    }
+
+    /// Get every pidfd referring to process `pid`, with the process holding it
+    pub fn pidfd_holders(&mut self, pid: &str) -> Result<Vec<DetailedFd>> {
+        let pid = Some(Self::parse_pid(pid)?);
+        self.detail_ls(|detail| *detail == FdDetail::PidFd { pid })
+    }
+
+    /// Get the processes that process `pid` holds pidfds to, as a
+    /// supervisor does for its children
+    pub fn pidfd_targets(&mut self, pid: &str) -> Result<Vec<PidFdTarget>> {
+        let info = self.process_ls(Self::parse_pid(pid)?.to_string())?;
+        Ok(info
+            .fds
+            .iter()
+            .filter_map(|entry| match entry.detail {
+                Some(FdDetail::PidFd { pid }) => Some(PidFdTarget { fd: entry.fd, pid, name: pid.and_then(process_name) }),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fdinfo() {
+        let eventfd = "pos:\t0\nflags:\t02\nmnt_id:\t15\nino:\t1057\neventfd-count:               2a\neventfd-id: 3\n";
+        assert_eq!(parse("anon_inode:[eventfd]", eventfd), Some(FdDetail::EventFd { count: 42 }));
+
+        let epoll = "pos:\t0\nflags:\t02\nmnt_id:\t15\nino:\t1057\n\
+tfd:        5 events:       19 data:                5  pos:0 ino:3a1 sdev:e\n\
+tfd:       12 events: 80000001 data:     7f00deadbeef  pos:0 ino:3a2 sdev:e\n";
+        let Some(FdDetail::Epoll(targets)) = parse("anon_inode:[eventpoll]", epoll) else {
+            panic!("epoll not decoded");
+        };
+        assert_eq!(targets[0], EpollTarget { fd: 5, events: 0x19, data: 5 });
+        assert_eq!(targets[1].events, 0x8000_0001);
+        assert_eq!(targets[1].data, 0x7f00_dead_beef);
+
+        let timerfd = "pos:\t0\nflags:\t02\nmnt_id:\t15\nino:\t1057\nclockid: 1\nticks: 3\nsettime flags: 01\nit_value: (0, 49406829)\nit_interval: (1, 0)\n";
+        let Some(FdDetail::TimerFd(timer)) = parse("anon_inode:[timerfd]", timerfd) else {
+            panic!("timerfd not decoded");
+        };
+        assert_eq!(timer.clock, Clock::Monotonic);
+        assert_eq!(timer.ticks, 3);
+        assert_eq!(timer.settime_flags, 1);
+        assert_eq!(timer.next_expiry, Some(Duration::from_nanos(49_406_829)));
+        assert_eq!(timer.interval, Duration::from_secs(1));
+
+        let signalfd = "pos:\t0\nflags:\t02\nmnt_id:\t15\nino:\t1057\nsigmask:\t0000000000014000\n";
+        assert_eq!(parse("anon_inode:[signalfd]", signalfd), Some(FdDetail::SignalFd { sigmask: 0x14000 }));
+
+        let inotify = "pos:\t0\nflags:\t00\nmnt_id:\t15\nino:\t1057\n\
+inotify wd:1 ino:2a sdev:800001 mask:fc6 ignored_mask:0 fhandle-bytes:8 fhandle-type:1 f_handle:2a0000005d7b2c1f\n\
+inotify wd:2 ino:1f sdev:fd00002 mask:2 ignored_mask:0\n";
+        let Some(FdDetail::Inotify(watches)) = parse("anon_inode:inotify", inotify) else {
+            panic!("inotify not decoded");
+        };
+        assert_eq!((watches[0].wd, watches[0].inode, watches[0].device), (1, 0x2a, (8, 1)));
+        assert_eq!(watches[0].mask, 0xfc6);
+        let handle = watches[0].handle.as_ref().unwrap();
+        assert_eq!((handle.handle_type, handle.bytes.len()), (1, 8));
+        assert_eq!(watches[1].device, (0xfd, 2));
+        assert!(watches[1].handle.is_none());
+
+        let fanotify = "pos:\t0\nflags:\t02\nmnt_id:\t15\nino:\t1057\n\
+fanotify flags:10 event-flags:0\n\
+fanotify ino:4f969 sdev:800013 mflags:0 mask:3b ignored_mask:0 fhandle-bytes:8 fhandle-type:1 f_handle:69f90400031e7b89\n\
+fanotify mnt_id:2a mflags:0 mask:20 ignored_mask:0\n\
+fanotify sdev:800013 mflags:0 mask:8 ignored_mask:0\n";
+        let Some(FdDetail::Fanotify(marks)) = parse("anon_inode:[fanotify]", fanotify) else {
+            panic!("fanotify not decoded");
+        };
+        assert_eq!(marks.len(), 3);
+        assert_eq!(marks[0].target, FanotifyTarget::Inode { inode: 0x4f969, device: (8, 0x13) });
+        assert_eq!(marks[0].mask, 0x3b);
+        assert_eq!(marks[1].target, FanotifyTarget::Mount(0x2a));
+        assert_eq!(marks[2].target, FanotifyTarget::Filesystem((8, 0x13)));
+
+        let map = "pos:\t0\nflags:\t02000002\nmnt_id:\t15\nino:\t1057\nmap_type:\t1\nkey_size:\t4\nvalue_size:\t8\nmax_entries:\t1024\nmap_flags:\t0x0\nmap_extra:\t0x0\nmemlock:\t12288\nmap_id:\t42\nfrozen:\t0\n";
+        let prog = "pos:\t0\nflags:\t02000002\nmnt_id:\t15\nino:\t1057\nprog_type:\t8\nprog_jited:\t1\nprog_tag:\ta04f5eef06a7f555\nmemlock:\t4096\nprog_id:\t17\nrun_time_ns:\t0\nrun_cnt:\t0\n";
+        let uring = "pos:\t0\nflags:\t02000002\nmnt_id:\t16\nino:\t1057\nSqMask:\t0x7\nSqHead:\t0\nSqThread:\t4321\nSqThreadCpu:\t2\nUserFiles:\t2\n    0: /var/log/app.log\n    3: /tmp/a\\040b\nUserBufs:\t0\nPollList:\n";
+        assert_eq!(
+            parse("anon_inode:bpf-map", map),
+            Some(FdDetail::BpfMap(BpfMap { map_type: 1, map_id: 42, key_size: 4, value_size: 8, max_entries: 1024 }))
+        );
+        let Some(FdDetail::BpfProg(prog)) = parse("anon_inode:bpf-prog", prog) else {
+            panic!("bpf-prog not decoded");
+        };
+        assert_eq!((prog.prog_type, prog.prog_id, prog.tag.as_str(), prog.jited), (8, 17, "a04f5eef06a7f555", true));
+        let Some(FdDetail::IoUring(uring)) = parse("anon_inode:[io_uring]", uring) else {
+            panic!("io_uring not decoded");
+        };
+        assert_eq!((uring.sq_thread, uring.sq_thread_cpu), (Some(4321), Some(2)));
+        assert_eq!(uring.files, [(0, String::from("/var/log/app.log")), (3, String::from("/tmp/a b"))]);
+        let no_thread = "SqThread:\t-1\nSqThreadCpu:\t-1\nUserFiles:\t0\nUserBufs:\t0\n";
+        let Some(FdDetail::IoUring(uring)) = parse("anon_inode:[io_uring]", no_thread) else {
+            panic!("io_uring not decoded");
+        };
+        assert_eq!((uring.sq_thread, uring.sq_thread_cpu), (None, None));
+
+        let pidfd = "pos:\t0\nflags:\t02000002\nmnt_id:\t4\nino:\t28816\nPid:\t28815\nNSpid:\t28815\n";
+        assert_eq!(parse("anon_inode:[pidfd]", pidfd), Some(FdDetail::PidFd { pid: Some(28815) }));
+        assert_eq!(parse("anon_inode:[pidfd]", "Pid:\t-1\nNSpid:\t-1\n"), Some(FdDetail::PidFd { pid: None }));
+
+        assert_eq!(parse("/dev/null", eventfd), None);
+        assert_eq!(parse("anon_inode:[userfaultfd]", eventfd), None);
+    }
+
+    #[test]
+    fn test_live_details() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = std::env::temp_dir().join(format!("minilsof-inotify-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let c_dir = CString::new(dir.as_os_str().as_bytes()).unwrap();
+        // SAFETY: eventfd and inotify_init1 take no pointers and return a new fd or -1
+        let eventfd = owned_fd(unsafe { libc::eventfd(7, libc::EFD_CLOEXEC) }).unwrap();
+        let inotify = owned_fd(unsafe { libc::inotify_init1(libc::IN_CLOEXEC) }).unwrap();
+        // SAFETY: `c_dir` is a valid C string
+        assert!(unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), c_dir.as_ptr(), libc::IN_CREATE) } >= 0);
+
+        let mut d = crate::LsofData::new();
+        let info = d.process_ls(std::process::id().to_string()).unwrap();
+        let detail = |fd: &std::os::fd::OwnedFd| {
+            info.fds.iter().find(|entry| entry.fd == fd.as_raw_fd() as u32).and_then(|entry| entry.detail.clone())
+        };
+        assert_eq!(detail(&eventfd), Some(FdDetail::EventFd { count: 7 }));
+
+        let Some(FdDetail::Inotify(watches)) = detail(&inotify) else {
+            panic!("inotify not decoded");
+        };
+        let meta = fs::metadata(&dir).unwrap();
+        assert_eq!(watches.len(), 1);
+        assert_eq!((watches[0].inode, watches[0].device), (meta.ino(), mountinfo::dev_major_minor(meta.dev())));
+        assert_eq!(watches[0].mask & libc::IN_CREATE, libc::IN_CREATE);
+        // Resolving needs CAP_DAC_READ_SEARCH
+        if let Some(path) = &watches[0].path {
+            assert_eq!(fs::metadata(path).unwrap().ino(), meta.ino());
+        }
+
+        // Scans of every process leave the paths out
+        let all = d.file_ls().unwrap();
+        let entry = all[&std::process::id().to_string()].fds.iter().find(|e| e.fd == inotify.as_raw_fd() as u32).unwrap();
+        let Some(FdDetail::Inotify(watches)) = &entry.detail else {
+            panic!("inotify not decoded");
+        };
+        assert!(watches[0].path.is_none());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_io_uring_ls() {
+        // struct io_uring_params, all zero for defaults
+        let mut params = [0u8; 120];
+        // SAFETY: `params` is valid for writes of a whole io_uring_params
+        let raw = unsafe { libc::syscall(libc::SYS_io_uring_setup, 4u32, params.as_mut_ptr()) } as libc::c_int;
+        let Ok(ring) = owned_fd(raw) else {
+            // Disabled by kernel config or sysctl
+            return;
+        };
+        let mut d = crate::LsofData::new();
+        let me = std::process::id().to_string();
+        let rings = d.io_uring_ls().unwrap();
+        let found = rings.iter().find(|r| r.pid == me && r.fd == ring.as_raw_fd() as u32).expect("io_uring not found");
+        assert_eq!(found.detail, FdDetail::IoUring(IoUring { sq_thread: None, sq_thread_cpu: None, files: Vec::new() }));
+        assert!(d.bpf_ls().unwrap().iter().all(|b| b.detail.is_bpf()));
+    }
+
+    #[test]
+    fn test_pidfd() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        // SAFETY: pidfd_open takes no pointers and returns a new fd or -1
+        let raw = unsafe { libc::syscall(libc::SYS_pidfd_open, child.id() as libc::pid_t, 0) } as libc::c_int;
+        let Ok(pidfd) = owned_fd(raw) else {
+            child.kill().unwrap();
+            child.wait().unwrap();
+            return;
+        };
+        let me = std::process::id().to_string();
+        let fd = pidfd.as_raw_fd() as u32;
+
+        let mut d = crate::LsofData::new();
+        let holders = d.pidfd_holders(&child.id().to_string()).unwrap();
+        assert!(holders.iter().any(|h| h.pid == me && h.fd == fd));
+        let targets = d.pidfd_targets(&me).unwrap();
+        let target = targets.iter().find(|t| t.fd == fd).expect("pidfd not found");
+        assert_eq!(target.pid, Some(child.id()));
+        assert_eq!(target.name.as_deref(), Some("sleep"));
+        assert!(matches!(d.pidfd_holders("sleep"), Err(crate::Error::InvalidPid(_))));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
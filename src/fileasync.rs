@@ -1,17 +1,63 @@
 #[cfg(feature = "async")]
 use tokio::task;
-use crate::{Fdinfo, LsofData, Result};
+use crate::audit::Finding;
+use crate::deleted::DeletedReport;
+use crate::fdinfo::{DetailedFd, PidFdTarget};
+use crate::graph::{GraphFilter, IpcGraph};
+use crate::library::Library;
+use crate::ipc::{InetConnection, Pipe, PipeEnd, SocketEnd, UnixConnection};
+use crate::locks::LockReport;
+use crate::memory::MemoryReport;
+use crate::namespace::PinnedNamespace;
+use crate::notify::Watcher;
+use crate::stale::StaleReport;
+use crate::watch::{self, Backoff, Holders, Query, WaitOutcome, WatchEvent};
+use crate::{Diagnostics, Fdinfo, LsofData, Result, Scan};
 use std::collections::HashMap;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{self, MissedTickBehavior};
 
 /// Asynchronous wrapper functions for LsofData
 /// 
 /// This implementation uses tokio to run the blocking operations
 /// in a separate thread pool to avoid blocking the async runtime.
+///
+/// Clones share the scanner but each keeps the diagnostics of its own last
+/// query, so give every task its own clone to read them reliably.
 #[cfg(feature = "async")]
 pub struct LsofAsync {
     inner: Arc<Mutex<LsofData>>,
+    /// Diagnostics of the last query made through this handle
+    diagnostics: Arc<Mutex<Diagnostics>>,
+}
+
+#[cfg(feature = "async")]
+impl Clone for LsofAsync {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            diagnostics: Arc::default(),
+        }
+    }
+}
+
+/// Stream of changes produced by [`LsofAsync::watch`]
+///
+/// Polling stops once the stream is dropped.
+pub struct Watch {
+    events: mpsc::Receiver<Result<WatchEvent>>,
+}
+
+impl Watch {
+    /// Wait for the next change, or a scan error
+    ///
+    /// Returns `None` once polling has stopped.
+    pub async fn next(&mut self) -> Option<Result<WatchEvent>> {
+        self.events.recv().await
+    }
 }
 
 #[cfg(feature = "async")]
@@ -20,57 +66,242 @@ impl LsofAsync {
     pub fn new() -> Self {
         Self {
             inner: Arc::new(Mutex::new(LsofData::new())),
+            diagnostics: Arc::default(),
         }
     }
 
-    /// Get information about all open files by all processes
-    pub async fn file_ls(&self) -> Result<HashMap<String, Fdinfo>> {
+    /// Run a query against the shared scanner on the blocking thread pool
+    ///
+    /// Every query resets the scanner before walking `/proc`, so a lock
+    /// poisoned by a panicking query holds no state worth discarding and is
+    /// simply taken over.
+    async fn run<T, F>(&self, query: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut LsofData) -> Result<T> + Send + 'static,
+    {
         let inner = self.inner.clone();
-        
+        let diagnostics = self.diagnostics.clone();
+
         task::spawn_blocking(move || {
-            let mut data = inner.lock().map_err(|_| 
-                crate::Error::Other("Failed to acquire lock".to_string())
-            )?;
-            
-            let result = data.file_ls().ok_or_else(|| 
-                crate::Error::Other("Failed to list all files".to_string())
-            )?;
-            
-            // Clone the result to avoid returning a reference to data inside the lock
-            Ok(result.clone())
-        }).await.map_err(|_| crate::Error::Other("Task join error".to_string()))?
+            let mut data = inner.lock().unwrap_or_else(PoisonError::into_inner);
+            let result = query(&mut data);
+            // Taken under the scanner lock, so they match this query's scan
+            *diagnostics.lock().unwrap_or_else(PoisonError::into_inner) = data.diagnostics().clone();
+            result
+        }).await?
+    }
+
+    /// Parts of the last query's scan through this handle that could not be read
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Get information about all open files by all processes
+    pub async fn file_ls(&self) -> Result<HashMap<String, Fdinfo>> {
+        // Clone the result to avoid returning a reference to data inside the lock
+        self.run(|data| data.file_ls().cloned()).await
     }
 
     /// Get information about processes using a specific file
-    pub async fn target_file_ls(&self, path: impl AsRef<Path> + Send + 'static) -> Result<Vec<Fdinfo>> {
+    pub async fn target_file_ls(&self, path: impl AsRef<Path>) -> Result<Vec<Fdinfo>> {
         let path_str = path.as_ref().to_string_lossy().to_string();
-        let inner = self.inner.clone();
-        
-        task::spawn_blocking(move || {
-            let mut data = inner.lock().map_err(|_| 
-                crate::Error::Other("Failed to acquire lock".to_string())
-            )?;
-            
-            data.target_file_ls(path_str).ok_or_else(|| 
-                crate::Error::Other(format!("Failed to list file: {}", path.as_ref().display()))
-            )
-        }).await.map_err(|_| crate::Error::Other("Task join error".to_string()))?
+
+        self.run(move |data| data.target_file_ls(path_str)).await
     }
 
     /// Get information about processes using a specific port
-    pub async fn port_ls(&self, port: impl AsRef<str> + Send + 'static) -> Result<Vec<Fdinfo>> {
+    pub async fn port_ls(&self, port: impl AsRef<str>) -> Result<Vec<Fdinfo>> {
         let port_str = port.as_ref().to_string();
-        let inner = self.inner.clone();
-        
-        task::spawn_blocking(move || {
-            let mut data = inner.lock().map_err(|_| 
-                crate::Error::Other("Failed to acquire lock".to_string())
-            )?;
-            
-            data.port_ls(port_str).ok_or_else(|| 
-                crate::Error::Other(format!("Failed to list port: {}", port.as_ref()))
-            )
-        }).await.map_err(|_| crate::Error::Other("Task join error".to_string()))?
+
+        self.run(move |data| data.port_ls(port_str)).await
+    }
+
+    /// Scan all processes and return their open files with diagnostics
+    ///
+    /// Prefer this over `file_ls` when the caller needs to know what the
+    /// scan could not read, since the scanner is shared between tasks.
+    pub async fn scan(&self) -> Result<Scan> {
+        self.run(|data| data.scan()).await
+    }
+
+    /// Get files that were unlinked but are still held open
+    pub async fn deleted_ls(&self) -> Result<DeletedReport> {
+        self.run(|data| data.deleted_ls()).await
+    }
+
+    /// Get processes running outdated executables or libraries
+    pub async fn stale_ls(&self) -> Result<StaleReport> {
+        self.run(|data| data.stale_ls()).await
+    }
+
+    /// Get the mapped files matching a glob pattern and the processes mapping them
+    pub async fn library_ls(&self, pattern: impl AsRef<str>) -> Result<Vec<Library>> {
+        let pattern = pattern.as_ref().to_string();
+        self.run(move |data| data.library_ls(&pattern)).await
+    }
+
+    /// Get the memory use of every process, per mapped file
+    pub async fn memory_ls(&self) -> Result<MemoryReport> {
+        self.run(|data| data.memory_ls()).await
+    }
+
+    /// Check the mappings of every process for signs of injected or tampered code
+    pub async fn audit(&self) -> Result<Vec<Finding>> {
+        self.run(|data| data.audit()).await
+    }
+
+    /// Get the locks held and waited for on a file
+    pub async fn lock_ls(&self, path: impl AsRef<Path>) -> Result<LockReport> {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |data| data.lock_ls(path)).await
+    }
+
+    /// Get every pipe and FIFO with the descriptors referring to it
+    pub async fn pipe_ls(&self) -> Result<Vec<Pipe>> {
+        self.run(|data| data.pipe_ls()).await
+    }
+
+    /// Get the other ends of the pipe behind a descriptor of a process
+    pub async fn pipe_peers(&self, pid: impl AsRef<str>, fd: u32) -> Result<Vec<PipeEnd>> {
+        let pid = pid.as_ref().to_string();
+        self.run(move |data| data.pipe_peers(&pid, fd)).await
+    }
+
+    /// Get every unix socket held by a process, with the processes at the other end
+    pub async fn unix_ls(&self) -> Result<Vec<UnixConnection>> {
+        self.run(|data| data.unix_ls()).await
+    }
+
+    /// Get the processes at the other end of the unix socket behind a descriptor
+    pub async fn unix_peers(&self, pid: impl AsRef<str>, fd: u32) -> Result<Option<Vec<SocketEnd>>> {
+        let pid = pid.as_ref().to_string();
+        self.run(move |data| data.unix_peers(&pid, fd)).await
+    }
+
+    /// Get every TCP and UDP socket held by a process, with the process at the other end
+    pub async fn inet_ls(&self) -> Result<Vec<InetConnection>> {
+        self.run(|data| data.inet_ls()).await
+    }
+
+    /// Get the processes at the other end of the TCP or UDP socket behind a descriptor
+    pub async fn inet_peers(&self, pid: impl AsRef<str>, fd: u32) -> Result<Vec<SocketEnd>> {
+        let pid = pid.as_ref().to_string();
+        self.run(move |data| data.inet_peers(&pid, fd)).await
+    }
+
+    /// Build a graph of the processes and the IPC resources they share
+    pub async fn ipc_graph(&self, filter: GraphFilter) -> Result<IpcGraph> {
+        self.run(move |data| data.ipc_graph(&filter)).await
+    }
+
+    /// Get the processes watching a file or directory through inotify or fanotify
+    pub async fn watchers_ls(&self, path: impl AsRef<Path>) -> Result<Vec<Watcher>> {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |data| data.watchers_ls(&path)).await
+    }
+
+    /// Get every descriptor of a BPF map or program
+    pub async fn bpf_ls(&self) -> Result<Vec<DetailedFd>> {
+        self.run(|data| data.bpf_ls()).await
+    }
+
+    /// Get every descriptor of an io_uring instance
+    pub async fn io_uring_ls(&self) -> Result<Vec<DetailedFd>> {
+        self.run(|data| data.io_uring_ls()).await
+    }
+
+    /// Get every pidfd referring to a process
+    pub async fn pidfd_holders(&self, pid: impl AsRef<str>) -> Result<Vec<DetailedFd>> {
+        let pid = pid.as_ref().to_string();
+        self.run(move |data| data.pidfd_holders(&pid)).await
+    }
+
+    /// Get the processes a process holds pidfds to
+    pub async fn pidfd_targets(&self, pid: impl AsRef<str>) -> Result<Vec<PidFdTarget>> {
+        let pid = pid.as_ref().to_string();
+        self.run(move |data| data.pidfd_targets(&pid)).await
+    }
+
+    /// Get the namespaces kept alive only by descriptors or bind mounts
+    pub async fn namespace_ls(&self) -> Result<Vec<PinnedNamespace>> {
+        self.run(|data| data.namespace_ls()).await
+    }
+
+    /// Get information about the open files of a single process
+    pub async fn process_ls(&self, pid: impl AsRef<str>) -> Result<Fdinfo> {
+        let pid_str = pid.as_ref().to_string();
+        self.run(move |data| data.process_ls(pid_str)).await
+    }
+
+    /// Poll a query with backoff until `done` accepts its holders
+    async fn wait_until(&self, query: Query, timeout: Duration, done: impl Fn(&[Fdinfo]) -> bool) -> Result<WaitOutcome> {
+        let mut backoff = Backoff::new(timeout);
+        loop {
+            let polled = query.clone();
+            let holders = watch::sorted(self.run(move |data| data.holders(&polled)).await?);
+            if done(&holders) {
+                return Ok(WaitOutcome::Satisfied(holders));
+            }
+            match backoff.next_delay() {
+                Some(delay) => time::sleep(delay).await,
+                None => return Ok(WaitOutcome::TimedOut(holders)),
+            }
+        }
+    }
+
+    /// Wait until no process holds the target, or `timeout` expires
+    pub async fn wait_released(&self, query: Query, timeout: Duration) -> Result<WaitOutcome> {
+        self.wait_until(query, timeout, |holders| holders.is_empty()).await
+    }
+
+    /// Wait until some process holds the target, or `timeout` expires
+    pub async fn wait_held(&self, query: Query, timeout: Duration) -> Result<WaitOutcome> {
+        self.wait_until(query, timeout, |holders| !holders.is_empty()).await
+    }
+
+    /// Wait until process `pid` holds the target, or `timeout` expires
+    pub async fn wait_held_by(&self, query: Query, pid: impl AsRef<str>, timeout: Duration) -> Result<WaitOutcome> {
+        self.wait_until(query, timeout, |holders| holders.iter().any(|info| info.pid == pid.as_ref())).await
+    }
+
+    /// Poll a query every `interval` and stream changes in its holders
+    ///
+    /// The first poll reports every current holder as acquired. Must be
+    /// called from within a Tokio runtime.
+    pub fn watch(&self, query: Query, interval: Duration) -> Watch {
+        let (tx, events) = mpsc::channel(64);
+        let lsof = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut known = Holders::new();
+
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = tx.closed() => return,
+                }
+
+                let polled = query.clone();
+                let changes = match lsof.run(move |data| data.holders(&polled)).await {
+                    Ok(holders) => {
+                        let changes = watch::changes(&known, &holders);
+                        known = holders;
+                        changes.into_iter().map(Ok).collect()
+                    }
+                    Err(err) => vec![Err(err)],
+                };
+                for change in changes {
+                    if tx.send(change).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Watch { events }
     }
 }
 
@@ -112,4 +343,59 @@ mod tests {
             println!("Found {} processes using port 80", processes.len());
         }
     }
+
+    #[tokio::test]
+    async fn test_async_queries_share_scanner() {
+        let lsof = LsofAsync::new();
+        let (all, by_port) = tokio::join!(lsof.file_ls(), lsof.port_ls("1"));
+        assert!(all.is_ok());
+        // Nothing listens on port 1, so the full listing must not leak into it
+        assert_eq!(by_port.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_async_diagnostics_per_handle() {
+        let lsof = LsofAsync::new();
+        let other = lsof.clone();
+        let scan = lsof.scan().await.unwrap();
+        assert_eq!(lsof.diagnostics(), scan.diagnostics);
+        // A clone that has not queried yet has nothing to report
+        assert_eq!(other.diagnostics(), Diagnostics::default());
+    }
+
+    async fn next(watch: &mut Watch) -> WatchEvent {
+        time::timeout(Duration::from_secs(10), watch.next()).await.unwrap().unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_async_watch_file() {
+        let path = std::env::temp_dir().join(format!("minilsof-watch-{}", std::process::id()));
+        std::fs::File::create(&path).unwrap();
+        let me = std::process::id().to_string();
+
+        let lsof = LsofAsync::new();
+        let mut watch = lsof.watch(Query::File(path.clone()), Duration::from_millis(20));
+        let file = std::fs::File::open(&path).unwrap();
+
+        assert!(matches!(next(&mut watch).await, WatchEvent::Acquired(info) if info.pid == me));
+        drop(file);
+        assert!(matches!(next(&mut watch).await, WatchEvent::Released(info) if info.pid == me));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_wait_held() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let me = std::process::id().to_string();
+
+        let lsof = LsofAsync::new();
+        let held = lsof.wait_held_by(Query::Port(port.clone()), &me, Duration::from_secs(5)).await.unwrap();
+        assert!(matches!(held, WaitOutcome::Satisfied(_)));
+
+        drop(listener);
+        let released = lsof.wait_released(Query::Port(port), Duration::from_secs(5)).await.unwrap();
+        assert_eq!(released, WaitOutcome::Satisfied(vec![]));
+    }
 }
@@ -1,39 +1,273 @@
-use crate::{Fdinfo, LsofData, Result};
+use crate::audit::Finding;
+use crate::deleted::DeletedReport;
+use crate::fdinfo::{DetailedFd, PidFdTarget};
+use crate::graph::{GraphFilter, IpcGraph};
+use crate::library::Library;
+use crate::ipc::{InetConnection, Pipe, PipeEnd, SocketEnd, UnixConnection};
+use crate::locks::LockReport;
+use crate::memory::MemoryReport;
+use crate::namespace::PinnedNamespace;
+use crate::notify::Watcher;
+use crate::probe::{self, Limitation, Visibility};
+use crate::stale::StaleReport;
+use crate::watch::{self, Backoff, Query, WaitOutcome};
+use crate::{Diagnostics, Error, Fdinfo, LsofData, Result, Scan};
 use std::collections::HashMap;
 use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// What [`LsofSync`] does when results would silently miss processes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VisibilityPolicy {
+    /// Run queries without probing
+    #[default]
+    Ignore,
+    /// Run queries and list the limitations in their diagnostics
+    Warn,
+    /// Fail queries with `Error::IncompleteVisibility`
+    Refuse,
+}
 
 /// Synchronous wrapper functions for LsofData
+///
+/// Every call runs against a fresh scan, so one instance can serve any
+/// sequence of queries.
 pub struct LsofSync {
     inner: LsofData,
+    /// What to do when the probe finds limitations
+    policy: VisibilityPolicy,
+    /// Result of the probe, taken before the first query that needs it
+    visibility: Option<Visibility>,
 }
 
 impl LsofSync {
     /// Create a new LsofSync instance
     pub fn new() -> Self {
+        Self::with_policy(VisibilityPolicy::Ignore)
+    }
+
+    /// Create a new LsofSync instance that probes visibility before queries
+    pub fn with_policy(policy: VisibilityPolicy) -> Self {
         Self {
             inner: LsofData::new(),
+            policy,
+            visibility: None,
+        }
+    }
+
+    /// What the calling process can see through `/proc`, probed once
+    pub fn visibility(&mut self) -> Result<&Visibility> {
+        let visibility = match self.visibility.take() {
+            Some(visibility) => visibility,
+            None => probe::probe()?,
+        };
+        Ok(self.visibility.insert(visibility))
+    }
+
+    /// Apply the visibility policy before a query
+    ///
+    /// `sockets` says whether the query reads the socket tables, the only
+    /// thing `subset=pid` hides.
+    fn check(&mut self, sockets: bool) -> Result<()> {
+        if self.policy == VisibilityPolicy::Ignore {
+            return Ok(());
+        }
+        let limitations: Vec<Limitation> = self
+            .visibility()?
+            .limitations()
+            .into_iter()
+            .filter(|limitation| sockets || *limitation != Limitation::SubsetPid)
+            .collect();
+
+        if self.policy == VisibilityPolicy::Refuse && !limitations.is_empty() {
+            return Err(Error::IncompleteVisibility(limitations));
         }
+        self.inner.limitations = limitations;
+        Ok(())
     }
 
     /// Get information about all open files by all processes
     pub fn file_ls(&mut self) -> Result<&HashMap<String, Fdinfo>> {
-        self.inner.file_ls().ok_or_else(|| crate::Error::Other("Failed to list all files".to_string()))
+        self.check(false)?;
+        self.inner.file_ls()
     }
 
     /// Get information about processes using a specific file
     pub fn target_file_ls(&mut self, path: impl AsRef<Path>) -> Result<Vec<Fdinfo>> {
+        self.check(false)?;
         let path_str = path.as_ref().to_string_lossy().to_string();
-        self.inner.target_file_ls(path_str).ok_or_else(|| 
-            crate::Error::Other(format!("Failed to list file: {}", path.as_ref().display()))
-        )
+        self.inner.target_file_ls(path_str)
     }
 
     /// Get information about processes using a specific port
     pub fn port_ls(&mut self, port: impl AsRef<str>) -> Result<Vec<Fdinfo>> {
+        self.check(true)?;
         let port_str = port.as_ref().to_string();
-        self.inner.port_ls(port_str).ok_or_else(|| 
-            crate::Error::Other(format!("Failed to list port: {}", port.as_ref()))
-        )
+        self.inner.port_ls(port_str)
+    }
+
+    /// Scan all processes and return their open files with diagnostics
+    pub fn scan(&mut self) -> Result<Scan> {
+        self.check(false)?;
+        self.inner.scan()
+    }
+
+    /// Get files that were unlinked but are still held open
+    pub fn deleted_ls(&mut self) -> Result<DeletedReport> {
+        self.check(false)?;
+        self.inner.deleted_ls()
+    }
+
+    /// Get processes running outdated executables or libraries
+    pub fn stale_ls(&mut self) -> Result<StaleReport> {
+        self.check(false)?;
+        self.inner.stale_ls()
+    }
+
+    /// Get the mapped files matching a glob pattern and the processes mapping them
+    pub fn library_ls(&mut self, pattern: &str) -> Result<Vec<Library>> {
+        self.check(false)?;
+        self.inner.library_ls(pattern)
+    }
+
+    /// Get the memory use of every process, per mapped file
+    pub fn memory_ls(&mut self) -> Result<MemoryReport> {
+        self.check(false)?;
+        self.inner.memory_ls()
+    }
+
+    /// Check the mappings of every process for signs of injected or tampered code
+    pub fn audit(&mut self) -> Result<Vec<Finding>> {
+        self.check(false)?;
+        self.inner.audit()
+    }
+
+    /// Get the locks held and waited for on a file
+    pub fn lock_ls(&mut self, path: impl AsRef<Path>) -> Result<LockReport> {
+        self.check(false)?;
+        self.inner.lock_ls(path)
+    }
+
+    /// Get every pipe and FIFO with the descriptors referring to it
+    pub fn pipe_ls(&mut self) -> Result<Vec<Pipe>> {
+        self.check(false)?;
+        self.inner.pipe_ls()
+    }
+
+    /// Get the other ends of the pipe behind a descriptor of a process
+    pub fn pipe_peers(&mut self, pid: &str, fd: u32) -> Result<Vec<PipeEnd>> {
+        self.check(false)?;
+        self.inner.pipe_peers(pid, fd)
+    }
+
+    /// Get every unix socket held by a process, with the processes at the other end
+    pub fn unix_ls(&mut self) -> Result<Vec<UnixConnection>> {
+        self.check(true)?;
+        self.inner.unix_ls()
+    }
+
+    /// Get the processes at the other end of the unix socket behind a descriptor
+    pub fn unix_peers(&mut self, pid: &str, fd: u32) -> Result<Option<Vec<SocketEnd>>> {
+        self.check(true)?;
+        self.inner.unix_peers(pid, fd)
+    }
+
+    /// Get every TCP and UDP socket held by a process, with the process at the other end
+    pub fn inet_ls(&mut self) -> Result<Vec<InetConnection>> {
+        self.check(true)?;
+        self.inner.inet_ls()
+    }
+
+    /// Get the processes at the other end of the TCP or UDP socket behind a descriptor
+    pub fn inet_peers(&mut self, pid: &str, fd: u32) -> Result<Vec<SocketEnd>> {
+        self.check(true)?;
+        self.inner.inet_peers(pid, fd)
+    }
+
+    /// Build a graph of the processes and the IPC resources they share
+    pub fn ipc_graph(&mut self, filter: &GraphFilter) -> Result<IpcGraph> {
+        self.check(true)?;
+        self.inner.ipc_graph(filter)
+    }
+
+    /// Get the processes watching a file or directory through inotify or fanotify
+    pub fn watchers_ls(&mut self, path: impl AsRef<Path>) -> Result<Vec<Watcher>> {
+        self.check(false)?;
+        self.inner.watchers_ls(path)
+    }
+
+    /// Get every descriptor of a BPF map or program
+    pub fn bpf_ls(&mut self) -> Result<Vec<DetailedFd>> {
+        self.check(false)?;
+        self.inner.bpf_ls()
+    }
+
+    /// Get every descriptor of an io_uring instance
+    pub fn io_uring_ls(&mut self) -> Result<Vec<DetailedFd>> {
+        self.check(false)?;
+        self.inner.io_uring_ls()
+    }
+
+    /// Get every pidfd referring to a process
+    pub fn pidfd_holders(&mut self, pid: &str) -> Result<Vec<DetailedFd>> {
+        self.check(false)?;
+        self.inner.pidfd_holders(pid)
+    }
+
+    /// Get the processes a process holds pidfds to
+    pub fn pidfd_targets(&mut self, pid: &str) -> Result<Vec<PidFdTarget>> {
+        self.check(false)?;
+        self.inner.pidfd_targets(pid)
+    }
+
+    /// Get the namespaces kept alive only by descriptors or bind mounts
+    pub fn namespace_ls(&mut self) -> Result<Vec<PinnedNamespace>> {
+        self.check(false)?;
+        self.inner.namespace_ls()
+    }
+
+    /// Parts of the last query's scan that could not be read
+    pub fn diagnostics(&self) -> &Diagnostics {
+        self.inner.diagnostics()
+    }
+
+    /// Get information about the open files of a single process
+    pub fn process_ls(&mut self, pid: impl AsRef<str>) -> Result<Fdinfo> {
+        self.inner.process_ls(pid.as_ref().to_string())
+    }
+}
+
+impl LsofSync {
+    /// Poll a query with backoff until `done` accepts its holders
+    fn wait_until(&mut self, query: &Query, timeout: Duration, done: impl Fn(&[Fdinfo]) -> bool) -> Result<WaitOutcome> {
+        self.check(matches!(query, Query::Port(_)))?;
+        let mut backoff = Backoff::new(timeout);
+        loop {
+            let holders = watch::sorted(self.inner.holders(query)?);
+            if done(&holders) {
+                return Ok(WaitOutcome::Satisfied(holders));
+            }
+            match backoff.next_delay() {
+                Some(delay) => thread::sleep(delay),
+                None => return Ok(WaitOutcome::TimedOut(holders)),
+            }
+        }
+    }
+
+    /// Block until no process holds the target, or `timeout` expires
+    pub fn wait_released(&mut self, query: Query, timeout: Duration) -> Result<WaitOutcome> {
+        self.wait_until(&query, timeout, |holders| holders.is_empty())
+    }
+
+    /// Block until some process holds the target, or `timeout` expires
+    pub fn wait_held(&mut self, query: Query, timeout: Duration) -> Result<WaitOutcome> {
+        self.wait_until(&query, timeout, |holders| !holders.is_empty())
+    }
+
+    /// Block until process `pid` holds the target, or `timeout` expires
+    pub fn wait_held_by(&mut self, query: Query, pid: impl AsRef<str>, timeout: Duration) -> Result<WaitOutcome> {
+        self.wait_until(&query, timeout, |holders| holders.iter().any(|info| info.pid == pid.as_ref()))
     }
 }
 
@@ -64,4 +298,45 @@ mod tests {
             println!("Found {} processes using /etc/passwd", processes.len());
         }
     }
+
+    #[test]
+    fn test_sync_wait_released() {
+        let path = std::env::temp_dir().join(format!("minilsof-wait-{}", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let me = std::process::id().to_string();
+        let mut lsof = LsofSync::new();
+
+        let query = Query::File(path.clone());
+        match lsof.wait_held_by(query.clone(), &me, Duration::from_secs(5)).unwrap() {
+            WaitOutcome::Satisfied(holders) => assert!(holders.iter().any(|info| info.pid == me)),
+            WaitOutcome::TimedOut(_) => panic!("file was never seen as held"),
+        }
+        match lsof.wait_released(query.clone(), Duration::from_millis(100)).unwrap() {
+            WaitOutcome::TimedOut(holders) => assert!(holders.iter().any(|info| info.pid == me)),
+            WaitOutcome::Satisfied(_) => panic!("file was released while still open"),
+        }
+
+        drop(file);
+        let released = lsof.wait_released(query, Duration::from_secs(5)).unwrap();
+        assert!(matches!(released, WaitOutcome::Satisfied(holders) if holders.iter().all(|info| info.pid != me)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sync_visibility_policy() {
+        let mut lsof = LsofSync::with_policy(VisibilityPolicy::Warn);
+        let limitations = lsof.visibility().unwrap().limitations();
+        assert!(lsof.file_ls().is_ok());
+        for limitation in &limitations {
+            if *limitation != Limitation::SubsetPid {
+                assert!(lsof.diagnostics().limitations.contains(limitation));
+            }
+        }
+
+        let mut lsof = LsofSync::with_policy(VisibilityPolicy::Refuse);
+        match lsof.port_ls("80") {
+            Err(Error::IncompleteVisibility(found)) => assert_eq!(found, limitations),
+            result => assert!(result.is_ok() && limitations.is_empty()),
+        }
+    }
 }
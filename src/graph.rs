@@ -0,0 +1,484 @@
+use crate::ipc::{self, IpcProcess, PipeRole, SocketEnd};
+use crate::maps::DELETED_SUFFIX;
+use crate::mountinfo;
+use crate::net::InetSocket;
+use crate::{LsofData, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+/// What a node of the graph stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// A process
+    Process,
+    /// An anonymous pipe or a FIFO
+    Pipe,
+    /// A unix socket
+    UnixSocket,
+    /// A TCP or UDP socket connected to a local peer
+    InetSocket,
+    /// A memfd, a POSIX shared memory file or a SysV segment
+    SharedMemory,
+}
+
+impl NodeKind {
+    /// Name used in the exports
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeKind::Process => "process",
+            NodeKind::Pipe => "pipe",
+            NodeKind::UnixSocket => "unix",
+            NodeKind::InetSocket => "inet",
+            NodeKind::SharedMemory => "shm",
+        }
+    }
+
+    /// Graphviz shape
+    fn shape(&self) -> &'static str {
+        match self {
+            NodeKind::Process => "box",
+            NodeKind::Pipe => "cds",
+            NodeKind::UnixSocket | NodeKind::InetSocket => "ellipse",
+            NodeKind::SharedMemory => "note",
+        }
+    }
+}
+
+/// What an edge of the graph stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// A process has a descriptor referring to a resource
+    Descriptor,
+    /// A process has shared memory mapped
+    Mapping,
+    /// Two sockets are the ends of one connection
+    Peer,
+}
+
+impl EdgeKind {
+    /// Name used in the exports
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeKind::Descriptor => "descriptor",
+            EdgeKind::Mapping => "mapping",
+            EdgeKind::Peer => "peer",
+        }
+    }
+}
+
+/// A process or resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// Unique ID, such as `pid:1234`, `pipe:5678` or `unix:91011`
+    pub id: String,
+    /// What the node stands for
+    pub kind: NodeKind,
+    /// Human-readable label
+    pub label: String,
+}
+
+/// A relationship between two nodes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Edge {
+    /// ID of the process, or of the first socket for peers
+    pub from: String,
+    /// ID of the resource, or of the second socket for peers
+    pub to: String,
+    /// What the edge stands for
+    pub kind: EdgeKind,
+    /// Descriptor number and access for descriptors, such as `3w`
+    pub label: String,
+}
+
+/// Processes and the IPC resources they share
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct IpcGraph {
+    /// Processes first, sorted by pid, then resources
+    pub nodes: Vec<Node>,
+    /// Edges, in the order they were found
+    pub edges: Vec<Edge>,
+}
+
+/// Which processes the graph is limited to
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct GraphFilter {
+    /// Only this process and its descendants
+    pub subtree: Option<String>,
+    /// Only processes in this cgroup or below it, such as `/system.slice`
+    pub cgroup: Option<String>,
+}
+
+impl GraphFilter {
+    /// Get the pids of the processes the filter lets through
+    fn select(&self, processes: &[IpcProcess]) -> HashSet<String> {
+        let mut pids: HashSet<String> = processes
+            .iter()
+            .filter(|process| match &self.cgroup {
+                Some(cgroup) => process.cgroup.as_deref().is_some_and(|path| in_cgroup(path, cgroup)),
+                None => true,
+            })
+            .map(|process| process.info.pid.clone())
+            .collect();
+
+        if let Some(root) = &self.subtree {
+            let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+            for process in processes {
+                if let Some(ppid) = &process.ppid {
+                    children.entry(ppid.as_str()).or_default().push(&process.info.pid);
+                }
+            }
+            let mut subtree = HashSet::new();
+            let mut stack = vec![root.as_str()];
+            while let Some(pid) = stack.pop() {
+                if subtree.insert(pid) {
+                    stack.extend(children.get(pid).into_iter().flatten());
+                }
+            }
+            pids.retain(|pid| subtree.contains(pid.as_str()));
+        }
+        pids
+    }
+}
+
+/// Whether cgroup `path` is `cgroup` or below it
+fn in_cgroup(path: &str, cgroup: &str) -> bool {
+    let cgroup = cgroup.trim_end_matches('/');
+    path.strip_prefix(cgroup).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Access letter of a pipe end, as lsof prints it
+fn role_letter(role: Option<PipeRole>) -> &'static str {
+    match role {
+        Some(PipeRole::Reader) => "r",
+        Some(PipeRole::Writer) => "w",
+        Some(PipeRole::ReadWrite) => "u",
+        None => "",
+    }
+}
+
+/// Escape a string for a double-quoted DOT ID
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Escape a string for a JSON string literal
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl IpcGraph {
+    /// Export to Graphviz DOT
+    ///
+    /// Mappings are drawn dotted, and peer edges dashed and undirected.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph ipc {\n");
+        for node in &self.nodes {
+            let _ = writeln!(
+                out,
+                "  \"{}\" [label=\"{}\", shape={}];",
+                dot_escape(&node.id),
+                dot_escape(&node.label),
+                node.kind.shape()
+            );
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Descriptor => "",
+                EdgeKind::Mapping => ", style=dotted",
+                EdgeKind::Peer => ", style=dashed, dir=none",
+            };
+            let _ = writeln!(
+                out,
+                "  \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                dot_escape(&edge.from),
+                dot_escape(&edge.to),
+                dot_escape(&edge.label),
+                style
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Export to JSON, as a list of nodes and the edges leaving each node
+    ///
+    /// The layout is `{"nodes": [{"id", "kind", "label"}], "adjacency":
+    /// {id: [{"to", "kind", "label"}]}}`, with every node in `adjacency`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"nodes\":[");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{}{{\"id\":\"{}\",\"kind\":\"{}\",\"label\":\"{}\"}}",
+                if i == 0 { "" } else { "," },
+                json_escape(&node.id),
+                node.kind.name(),
+                json_escape(&node.label)
+            );
+        }
+        out.push_str("],\"adjacency\":{");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let _ = write!(out, "{}\"{}\":[", if i == 0 { "" } else { "," }, json_escape(&node.id));
+            for (j, edge) in self.edges.iter().filter(|edge| edge.from == node.id).enumerate() {
+                let _ = write!(
+                    out,
+                    "{}{{\"to\":\"{}\",\"kind\":\"{}\",\"label\":\"{}\"}}",
+                    if j == 0 { "" } else { "," },
+                    json_escape(&edge.to),
+                    edge.kind.name(),
+                    json_escape(&edge.label)
+                );
+            }
+            out.push(']');
+        }
+        out.push_str("}}");
+        out
+    }
+}
+
+/// Accumulates the graph, adding process nodes as they are first used
+#[derive(Default)]
+struct GraphBuilder {
+    graph: IpcGraph,
+    nodes: HashSet<String>,
+    edges: HashSet<Edge>,
+    processes: BTreeMap<(u64, String), Option<String>>,
+}
+
+impl GraphBuilder {
+    /// Add a resource node, once
+    fn resource(&mut self, id: String, kind: NodeKind, label: String) {
+        if self.nodes.insert(id.clone()) {
+            self.graph.nodes.push(Node { id, kind, label });
+        }
+    }
+
+    /// Add an edge from a process, once
+    fn process_edge(&mut self, pid: &str, name: &Option<String>, to: &str, kind: EdgeKind, label: String) {
+        self.processes.entry((pid.parse().unwrap_or(u64::MAX), pid.to_string())).or_insert_with(|| name.clone());
+        self.edge(format!("pid:{}", pid), to.to_string(), kind, label);
+    }
+
+    /// Add an edge, once
+    fn edge(&mut self, from: String, to: String, kind: EdgeKind, label: String) {
+        let edge = Edge { from, to, kind, label };
+        if self.edges.insert(edge.clone()) {
+            self.graph.edges.push(edge);
+        }
+    }
+
+    /// Add the sockets of a connection, the descriptors holding them and the
+    /// peer edge between them
+    fn connection(&mut self, kind: NodeKind, socket: (u64, String), peer: Option<(u64, String)>, holders: &[SocketEnd], peers: &[SocketEnd]) {
+        let pids: HashSet<&str> = holders.iter().chain(peers).map(|end| end.pid.as_str()).collect();
+        if pids.len() < 2 {
+            return;
+        }
+        let prefix = kind.name();
+        let id = format!("{}:{}", prefix, socket.0);
+        self.resource(id.clone(), kind, socket.1);
+        for end in holders {
+            self.process_edge(&end.pid, &end.name, &id, EdgeKind::Descriptor, end.fd.to_string());
+        }
+        let Some((peer, label)) = peer else {
+            return;
+        };
+        let peer_id = format!("{}:{}", prefix, peer);
+        self.resource(peer_id.clone(), kind, label);
+        for end in peers {
+            self.process_edge(&end.pid, &end.name, &peer_id, EdgeKind::Descriptor, end.fd.to_string());
+        }
+        // Each pair is seen from both sockets; keep one direction
+        let (from, to) = if socket.0 < peer { (id, peer_id) } else { (peer_id, id) };
+        self.edge(from, to, EdgeKind::Peer, String::from("peer"));
+    }
+
+    /// Put the process nodes, sorted by pid, ahead of the resources
+    fn finish(mut self) -> IpcGraph {
+        let mut nodes: Vec<Node> = self
+            .processes
+            .into_iter()
+            .map(|((_, pid), name)| Node {
+                id: format!("pid:{}", pid),
+                kind: NodeKind::Process,
+                label: match name {
+                    Some(name) => format!("{} ({})", name, pid),
+                    None => pid,
+                },
+            })
+            .collect();
+        nodes.append(&mut self.graph.nodes);
+        self.graph.nodes = nodes;
+        self.graph
+    }
+}
+
+/// A shared memory object and the processes using it, keyed by device and inode
+type SharedMemory = BTreeMap<((u32, u32), u64), (String, Vec<(String, Option<String>, EdgeKind, String)>)>;
+
+/// Collect shared memory from descriptors and mappings
+fn shared_memory(processes: &[IpcProcess]) -> SharedMemory {
+    let mut objects: SharedMemory = BTreeMap::new();
+    for process in processes {
+        let mut add = |key, path: &str, kind, label| {
+            let name = path.strip_suffix(DELETED_SUFFIX).unwrap_or(path).to_string();
+            let (_, users) = objects.entry(key).or_insert_with(|| (name, Vec::new()));
+            users.push((process.info.pid.clone(), process.info.name.clone(), kind, label));
+        };
+        for (fd, stat) in &process.files {
+            let Some(entry) = process.info.fds.iter().find(|entry| entry.fd == *fd) else {
+                continue;
+            };
+            if ipc::is_shared_memory(&entry.link) {
+                let key = (mountinfo::dev_major_minor(stat.dev), stat.ino);
+                add(key, &entry.link, EdgeKind::Descriptor, fd.to_string());
+            }
+        }
+        for entry in &process.shared_maps {
+            if let Some(path) = &entry.path {
+                add((entry.device, entry.inode), path, EdgeKind::Mapping, String::from("mmap"));
+            }
+        }
+    }
+    objects
+}
+
+impl LsofData {
+    /// Build a graph of the processes and the IPC resources they share
+    ///
+    /// Resources are pipes and FIFOs, unix sockets, TCP and UDP connections
+    /// between local processes, and shared memory: memfds, files under
+    /// `/dev/shm` and SysV segments, whether held by descriptor or mapped.
+    /// Only resources shared by at least two processes let through by
+    /// `filter` are included, along with those processes.
+    pub fn ipc_graph(&mut self, filter: &GraphFilter) -> Result<IpcGraph> {
+        let mut scan = self.ipc_scan()?;
+        let selected = filter.select(&scan.processes);
+        scan.processes.retain(|process| selected.contains(&process.info.pid));
+
+        let mut builder = GraphBuilder::default();
+        for pipe in ipc::pipes(&scan) {
+            let pids: HashSet<&str> = pipe.ends.iter().map(|end| end.pid.as_str()).collect();
+            if pids.len() < 2 {
+                continue;
+            }
+            let (id, label) = match &pipe.fifo {
+                Some(path) => (format!("fifo:{}", path.display()), path.display().to_string()),
+                None => (format!("pipe:{}", pipe.inode), format!("pipe {}", pipe.inode)),
+            };
+            builder.resource(id.clone(), NodeKind::Pipe, label);
+            for end in &pipe.ends {
+                let label = format!("{}{}", end.fd, role_letter(end.role));
+                builder.process_edge(&end.pid, &end.name, &id, EdgeKind::Descriptor, label);
+            }
+        }
+
+        let (sockets, peers_known) = ipc::unix_sockets(&mut self.diagnostics)?;
+        let connections = ipc::unix_connections(&scan, sockets, peers_known);
+        let labels: HashMap<u64, String> = connections
+            .iter()
+            .map(|connection| (connection.socket.inode, connection.socket.path.clone().unwrap_or_else(|| String::from("unix"))))
+            .collect();
+        for connection in &connections {
+            let label = |inode| labels.get(&inode).cloned().unwrap_or_else(|| String::from("unix"));
+            let socket = (connection.socket.inode, label(connection.socket.inode));
+            let peer = connection.socket.peer.map(|peer| (peer, label(peer)));
+            let peers = connection.peers.as_deref().unwrap_or_default();
+            builder.connection(NodeKind::UnixSocket, socket, peer, &connection.holders, peers);
+        }
+
+        for connection in ipc::inet_connections(&scan) {
+            let label = |socket: &InetSocket| format!("{} {}", socket.protocol, socket.local);
+            let socket = (connection.socket.inode, label(&connection.socket));
+            let peer = connection.peer.as_ref().map(|peer| (peer.inode, label(peer)));
+            builder.connection(NodeKind::InetSocket, socket, peer, &connection.holders, &connection.peers);
+        }
+
+        for ((device, inode), (name, users)) in shared_memory(&scan.processes) {
+            let pids: HashSet<&str> = users.iter().map(|(pid, ..)| pid.as_str()).collect();
+            if pids.len() < 2 {
+                continue;
+            }
+            let id = format!("shm:{}:{}:{}", device.0, device.1, inode);
+            builder.resource(id.clone(), NodeKind::SharedMemory, name);
+            for (pid, process_name, kind, label) in users {
+                builder.process_edge(&pid, &process_name, &id, kind, label);
+            }
+        }
+        Ok(builder.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_export() {
+        let graph = IpcGraph {
+            nodes: vec![
+                Node { id: String::from("pid:1"), kind: NodeKind::Process, label: String::from("sh \"a\" (1)") },
+                Node { id: String::from("pipe:7"), kind: NodeKind::Pipe, label: String::from("pipe 7") },
+            ],
+            edges: vec![Edge {
+                from: String::from("pid:1"),
+                to: String::from("pipe:7"),
+                kind: EdgeKind::Descriptor,
+                label: String::from("1w"),
+            }],
+        };
+        assert_eq!(
+            graph.to_dot(),
+            "digraph ipc {\n  \"pid:1\" [label=\"sh \\\"a\\\" (1)\", shape=box];\n  \"pipe:7\" [label=\"pipe 7\", shape=cds];\n  \"pid:1\" -> \"pipe:7\" [label=\"1w\"];\n}\n"
+        );
+        assert_eq!(
+            graph.to_json(),
+            "{\"nodes\":[{\"id\":\"pid:1\",\"kind\":\"process\",\"label\":\"sh \\\"a\\\" (1)\"},{\"id\":\"pipe:7\",\"kind\":\"pipe\",\"label\":\"pipe 7\"}],\
+             \"adjacency\":{\"pid:1\":[{\"to\":\"pipe:7\",\"kind\":\"descriptor\",\"label\":\"1w\"}],\"pipe:7\":[]}}"
+        );
+        assert_eq!(json_escape("a\u{1}b"), "a\\u0001b");
+        assert!(in_cgroup("/system.slice/app.service", "/system.slice"));
+        assert!(!in_cgroup("/system.slice2", "/system.slice"));
+        assert!(in_cgroup("/user.slice", "/"));
+    }
+
+    #[test]
+    fn test_ipc_graph() {
+        let mut child = Command::new("cat").stdin(Stdio::piped()).stdout(Stdio::null()).spawn().unwrap();
+        let me = std::process::id().to_string();
+
+        let mut d = LsofData::new();
+        let filter = GraphFilter { subtree: Some(me.clone()), cgroup: None };
+        let graph = d.ipc_graph(&filter).unwrap();
+        let child_id = format!("pid:{}", child.id());
+        let pipe = graph
+            .edges
+            .iter()
+            .find(|edge| edge.from == child_id && edge.label == "0r")
+            .expect("child's stdin pipe not in graph");
+        assert!(graph.edges.iter().any(|edge| edge.from == format!("pid:{}", me) && edge.to == pipe.to));
+        assert!(graph.nodes.iter().any(|node| node.id == pipe.to && node.kind == NodeKind::Pipe));
+        // Only our process and its children pass the filter
+        if me != "1" {
+            assert!(graph.nodes.iter().all(|node| node.id != "pid:1"));
+        }
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
@@ -0,0 +1,670 @@
+use crate::maps::MapEntry;
+use crate::mountinfo;
+use crate::net::{self, InetSocket, Protocol, SocketState};
+use crate::procfs::FileStat;
+use crate::sockdiag;
+use crate::probe::Limitation;
+use crate::{Diagnostics, Error, Fdinfo, LsofData, LsofFiletype, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::read_to_string;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+
+/// Which way data flows through a pipe end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PipeRole {
+    /// Opened for reading
+    Reader,
+    /// Opened for writing
+    Writer,
+    /// Opened for both, as FIFOs sometimes are
+    ReadWrite,
+}
+
+impl PipeRole {
+    /// Take the role from the open flags of a descriptor
+    pub fn from_flags(flags: u32) -> Option<Self> {
+        match flags as libc::c_int & libc::O_ACCMODE {
+            libc::O_RDONLY => Some(PipeRole::Reader),
+            libc::O_WRONLY => Some(PipeRole::Writer),
+            libc::O_RDWR => Some(PipeRole::ReadWrite),
+            _ => None,
+        }
+    }
+
+    /// Whether data can be read through this end
+    pub fn reads(&self) -> bool {
+        matches!(self, PipeRole::Reader | PipeRole::ReadWrite)
+    }
+
+    /// Whether data can be written through this end
+    pub fn writes(&self) -> bool {
+        matches!(self, PipeRole::Writer | PipeRole::ReadWrite)
+    }
+}
+
+/// A descriptor of a process referring to a pipe or FIFO
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipeEnd {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// Descriptor number
+    pub fd: u32,
+    /// Reader or writer, `None` if the fdinfo could not be read
+    pub role: Option<PipeRole>,
+}
+
+/// A pipe or FIFO and every descriptor referring to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipe {
+    /// Inode of the pipe
+    pub inode: u64,
+    /// Path of the FIFO, `None` for an anonymous pipe
+    pub fifo: Option<PathBuf>,
+    /// Descriptors referring to the pipe, sorted by pid and fd
+    pub ends: Vec<PipeEnd>,
+}
+
+impl Pipe {
+    /// Ends data can be read from
+    pub fn readers(&self) -> impl Iterator<Item = &PipeEnd> {
+        self.ends.iter().filter(|end| end.role.is_some_and(|role| role.reads()))
+    }
+
+    /// Ends data can be written to
+    pub fn writers(&self) -> impl Iterator<Item = &PipeEnd> {
+        self.ends.iter().filter(|end| end.role.is_some_and(|role| role.writes()))
+    }
+}
+
+/// Type of a unix socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnixSocketType {
+    /// `SOCK_STREAM`
+    Stream,
+    /// `SOCK_DGRAM`
+    Datagram,
+    /// `SOCK_SEQPACKET`
+    SeqPacket,
+    /// Any other type, by number
+    Other(u8),
+}
+
+impl UnixSocketType {
+    /// Convert a `SOCK_*` number
+    pub(crate) fn from_raw(raw: u8) -> Self {
+        match raw as libc::c_int {
+            libc::SOCK_STREAM => UnixSocketType::Stream,
+            libc::SOCK_DGRAM => UnixSocketType::Datagram,
+            libc::SOCK_SEQPACKET => UnixSocketType::SeqPacket,
+            _ => UnixSocketType::Other(raw),
+        }
+    }
+}
+
+/// A unix socket as the kernel reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixSocket {
+    /// Inode of the socket, as in `socket:[inode]`
+    pub inode: u64,
+    /// Socket type
+    pub socket_type: UnixSocketType,
+    /// Whether the socket is listening for connections
+    pub listening: bool,
+    /// Bound path, with abstract names starting with `@`
+    pub path: Option<String>,
+    /// Inode of the socket at the other end of the connection
+    pub peer: Option<u64>,
+}
+
+/// Convert a raw `sun_path` to a display name, with `@` for abstract names
+pub(crate) fn unix_path(raw: &[u8]) -> String {
+    match raw.split_first() {
+        Some((0, name)) => format!("@{}", String::from_utf8_lossy(name)),
+        _ => {
+            let end = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+            String::from_utf8_lossy(&raw[..end]).into_owned()
+        }
+    }
+}
+
+/// Flag of a listening socket in `/proc/net/unix` (`__SO_ACCEPTCON`)
+const SO_ACCEPTCON: u32 = 1 << 16;
+
+/// Parse one line of `/proc/net/unix`; the path may contain spaces
+fn parse_net_unix_line(line: &str) -> Option<UnixSocket> {
+    let mut fields = Vec::with_capacity(7);
+    let mut rest = line;
+    for _ in 0..7 {
+        let (field, tail) = rest.trim_start().split_once(' ').unwrap_or((rest.trim_start(), ""));
+        fields.push(field);
+        rest = tail;
+    }
+    let path = rest.trim_start();
+
+    Some(UnixSocket {
+        inode: fields[6].parse().ok()?,
+        socket_type: UnixSocketType::from_raw(u8::from_str_radix(fields[4], 16).ok()?),
+        listening: u32::from_str_radix(fields[3], 16).ok()? & SO_ACCEPTCON != 0,
+        path: (!path.is_empty()).then(|| path.to_string()),
+        // The table does not say who is connected to whom
+        peer: None,
+    })
+}
+
+/// Parse the contents of `/proc/net/unix`, skipping the header and malformed lines
+pub fn parse_net_unix(content: &str) -> Vec<UnixSocket> {
+    content.lines().skip(1).filter_map(parse_net_unix_line).collect()
+}
+
+/// Get every unix socket in the caller's network namespace, and whether
+/// their peers are known
+///
+/// `sock_diag` is asked first since only it reports peers; without it the
+/// sockets come from `/proc/net/unix`, with peers unknown, and the failure
+/// is recorded in `diagnostics` as a limitation.
+pub(crate) fn unix_sockets(diagnostics: &mut Diagnostics) -> Result<(Vec<UnixSocket>, bool)> {
+    match sockdiag::unix_sockets() {
+        Ok(sockets) => Ok((sockets, true)),
+        Err(_) => {
+            diagnostics.limitations.push(Limitation::SockDiagUnavailable);
+            Ok((parse_net_unix(&read_to_string("/proc/net/unix")?), false))
+        }
+    }
+}
+
+/// Get the inode from a link such as `pipe:[1234]`, given its `pipe:[` prefix
+fn bracket_inode(link: &str, prefix: &str) -> Option<u64> {
+    link.strip_prefix(prefix)?.strip_suffix(']')?.parse().ok()
+}
+
+/// A descriptor of a process referring to a socket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketEnd {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// Descriptor number
+    pub fd: u32,
+}
+
+/// A unix socket held open by processes, and who holds the other end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixConnection {
+    /// The socket
+    pub socket: UnixSocket,
+    /// Descriptors referring to the socket, sorted by pid and fd
+    pub holders: Vec<SocketEnd>,
+    /// Descriptors referring to the peer socket, sorted by pid and fd
+    ///
+    /// `None` when peers are unknown: they are only reported by `sock_diag`,
+    /// and the `/proc/net/unix` fallback does not say who is connected to
+    /// whom. An unconnected socket has `Some` empty list.
+    pub peers: Option<Vec<SocketEnd>>,
+}
+
+/// A TCP or UDP socket held open by processes, and who holds the other end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InetConnection {
+    /// The socket
+    pub socket: InetSocket,
+    /// Descriptors referring to the socket, sorted by pid and fd
+    pub holders: Vec<SocketEnd>,
+    /// The socket at the other end, if it is local to the same network namespace
+    pub peer: Option<InetSocket>,
+    /// Descriptors referring to the peer socket, sorted by pid and fd
+    pub peers: Vec<SocketEnd>,
+}
+
+/// Key matching a connected socket to its peer: transport, then the
+/// peer's view of the local and remote endpoints
+type EndpointKey = (bool, SocketAddr, SocketAddr);
+
+/// Collect the descriptors of `infos` that refer to sockets, by inode
+fn socket_ends<'a>(infos: impl Iterator<Item = &'a Fdinfo>) -> HashMap<u64, Vec<SocketEnd>> {
+    let mut holders: HashMap<u64, Vec<SocketEnd>> = HashMap::new();
+    for info in infos {
+        for entry in &info.fds {
+            if let Some(inode) = bracket_inode(&entry.link, "socket:[") {
+                holders.entry(inode).or_default().push(SocketEnd {
+                    pid: info.pid.clone(),
+                    name: info.name.clone(),
+                    fd: entry.fd,
+                });
+            }
+        }
+    }
+    for ends in holders.values_mut() {
+        ends.sort_by_key(|end| end_order(&end.pid, end.fd));
+    }
+    holders
+}
+
+/// Sort key for descriptors of processes: numeric pid, then fd
+fn end_order(pid: &str, fd: u32) -> (u64, u32) {
+    (pid.parse().unwrap_or(u64::MAX), fd)
+}
+
+/// Identity of a pipe: anonymous pipes by inode, FIFOs by device and inode
+type PipeKey = (Option<(u32, u32)>, u64);
+
+/// What one pass over the processes collects about a process for IPC
+pub(crate) struct IpcProcess {
+    /// Descriptors of the process
+    pub(crate) info: Fdinfo,
+    /// Parent process ID
+    pub(crate) ppid: Option<String>,
+    /// cgroup path of the process
+    pub(crate) cgroup: Option<String>,
+    /// Network namespace, as the `ns/net` link
+    pub(crate) netns: PathBuf,
+    /// Descriptors whose link is a path, with the file behind them
+    pub(crate) files: Vec<(u32, FileStat)>,
+    /// Mappings of shared memory: memfds, `/dev/shm` files and SysV segments
+    pub(crate) shared_maps: Vec<MapEntry>,
+}
+
+/// What one pass over the processes collects for IPC
+pub(crate) struct IpcScan {
+    /// Every process that could be read
+    pub(crate) processes: Vec<IpcProcess>,
+    /// TCP and UDP sockets per network namespace
+    pub(crate) tables: HashMap<PathBuf, Vec<InetSocket>>,
+}
+
+/// Whether a mapped path is shared memory
+pub(crate) fn is_shared_memory(path: &str) -> bool {
+    path.starts_with("/memfd:") || path.starts_with("/dev/shm/") || path.starts_with("/SYSV")
+}
+
+/// Get the cgroup path from `/proc/<pid>/cgroup`, preferring the unified hierarchy
+fn cgroup_path(content: &str) -> Option<String> {
+    let paths: Vec<(&str, &str)> = content
+        .lines()
+        .filter_map(|line| {
+            let (hierarchy, rest) = line.split_once(':')?;
+            Some((hierarchy, rest.split_once(':')?.1))
+        })
+        .collect();
+    let (_, path) = paths.iter().find(|(hierarchy, _)| *hierarchy == "0").or(paths.first())?;
+    Some(path.to_string())
+}
+
+/// Group the descriptors referring to pipes and FIFOs
+pub(crate) fn pipes(scan: &IpcScan) -> Vec<Pipe> {
+    let mut pipes: BTreeMap<PipeKey, Pipe> = BTreeMap::new();
+    for process in &scan.processes {
+        for entry in &process.info.fds {
+            let (key, fifo) = match bracket_inode(&entry.link, "pipe:[") {
+                Some(inode) => ((None, inode), None),
+                None => match process.files.iter().find(|(fd, _)| *fd == entry.fd) {
+                    Some((_, stat)) if stat.mode & libc::S_IFMT == libc::S_IFIFO => {
+                        ((Some(mountinfo::dev_major_minor(stat.dev)), stat.ino), Some(PathBuf::from(&entry.link)))
+                    }
+                    _ => continue,
+                },
+            };
+            let end = PipeEnd {
+                pid: process.info.pid.clone(),
+                name: process.info.name.clone(),
+                fd: entry.fd,
+                role: entry.flags.and_then(PipeRole::from_flags),
+            };
+            pipes
+                .entry(key)
+                .or_insert_with(|| Pipe { inode: key.1, fifo, ends: Vec::new() })
+                .ends
+                .push(end);
+        }
+    }
+    let mut pipes: Vec<Pipe> = pipes.into_values().collect();
+    for pipe in &mut pipes {
+        pipe.ends.sort_by_key(|end| end_order(&end.pid, end.fd));
+    }
+    pipes
+}
+
+/// Join unix sockets to the descriptors holding them and their peers
+///
+/// `peers_known` says whether the sockets came with their peers.
+pub(crate) fn unix_connections(scan: &IpcScan, sockets: Vec<UnixSocket>, peers_known: bool) -> Vec<UnixConnection> {
+    let holders = socket_ends(scan.processes.iter().map(|process| &process.info));
+    let mut connections: Vec<UnixConnection> = sockets
+        .into_iter()
+        .filter_map(|socket| {
+            let ends = holders.get(&socket.inode)?.clone();
+            let peers = peers_known
+                .then(|| socket.peer.and_then(|peer| holders.get(&peer)).cloned().unwrap_or_default());
+            Some(UnixConnection { socket, holders: ends, peers })
+        })
+        .collect();
+    connections.sort_by_key(|connection| connection.socket.inode);
+    connections
+}
+
+/// Find the unconnected UDP socket receiving what a connected one sends to
+/// `remote`: one bound to that address, or else a wildcard bind on its port
+fn udp_receiver<'a>(bound: &HashMap<SocketAddr, &'a InetSocket>, remote: SocketAddr) -> Option<&'a InetSocket> {
+    let remote = net::canonical(remote);
+    let wildcard = |ip: IpAddr| bound.get(&SocketAddr::new(ip, remote.port()));
+    bound
+        .get(&remote)
+        .or_else(|| wildcard(Ipv4Addr::UNSPECIFIED.into()))
+        .or_else(|| wildcard(Ipv6Addr::UNSPECIFIED.into()))
+        .copied()
+}
+
+/// Join TCP and UDP sockets to the descriptors holding them, and match
+/// connected sockets to their peer within each network namespace
+pub(crate) fn inet_connections(scan: &IpcScan) -> Vec<InetConnection> {
+    let mut connections = Vec::new();
+    for (netns, sockets) in &scan.tables {
+        let infos = scan.processes.iter().filter(|process| &process.netns == netns).map(|process| &process.info);
+        let holders = socket_ends(infos);
+        let endpoints: HashMap<EndpointKey, &InetSocket> = sockets
+            .iter()
+            // Sockets in TIME_WAIT have no inode and no holder left
+            .filter(|socket| socket.state != SocketState::Listen && socket.inode != 0)
+            .map(|socket| {
+                let key = (socket.protocol.is_tcp(), net::canonical(socket.local), net::canonical(socket.remote));
+                (key, socket)
+            })
+            .collect();
+        // Unconnected UDP sockets by bound address, for clients of a UDP server
+        let bound: HashMap<SocketAddr, &InetSocket> = sockets
+            .iter()
+            .filter(|socket| !socket.protocol.is_tcp() && socket.remote.port() == 0 && socket.inode != 0)
+            .map(|socket| (net::canonical(socket.local), socket))
+            .collect();
+
+        for socket in sockets {
+            let Some(ends) = holders.get(&socket.inode) else {
+                continue;
+            };
+            // Listening and unconnected sockets have no peer
+            let peer = (socket.state != SocketState::Listen && socket.remote.port() != 0)
+                .then(|| {
+                    let key = (socket.protocol.is_tcp(), net::canonical(socket.remote), net::canonical(socket.local));
+                    let exact = endpoints.get(&key).copied();
+                    // A UDP server usually leaves its socket unconnected
+                    exact.or_else(|| (!socket.protocol.is_tcp()).then(|| udp_receiver(&bound, socket.remote)).flatten())
+                })
+                .flatten();
+            connections.push(InetConnection {
+                socket: socket.clone(),
+                holders: ends.clone(),
+                peer: peer.cloned(),
+                peers: peer.and_then(|peer| holders.get(&peer.inode)).cloned().unwrap_or_default(),
+            });
+        }
+    }
+    connections.sort_by_key(|connection| connection.socket.inode);
+    connections
+}
+
+impl LsofData {
+    /// Read every process once for everything IPC resolution needs
+    ///
+    /// The TCP and UDP tables are read once per network namespace, through
+    /// `/proc/<pid>/net` of a process in it.
+    pub(crate) fn ipc_scan(&mut self) -> Result<IpcScan> {
+        // Descriptors only; the few mappings of interest are read below
+        self.target_filetype = Some(LsofFiletype::Socket);
+        self.target_filename = String::new();
+
+        let mut tables: HashMap<PathBuf, Vec<InetSocket>> = HashMap::new();
+        let processes = self.visit_processes(|data, pid, process, diagnostics| {
+            let info = data.scan_process(pid, process, diagnostics)?;
+            let ppid = data.get_pid_info(process, pid)?.remove("PPid");
+            let netns = process.read_link("ns/net").map_err(|err| Self::pid_error(pid, err))?;
+            if !tables.contains_key(&netns) {
+                let mut sockets = Vec::new();
+                for protocol in Protocol::ALL {
+                    let name = format!("net/{}", protocol.file_name());
+                    match process.read_to_string(&name) {
+                        Ok(content) => sockets.extend(net::parse(&content, protocol)),
+                        Err(err) => diagnostics.unreadable(format!("/proc/{}/{}", pid, name), &err),
+                    }
+                }
+                tables.insert(netns.clone(), sockets);
+            }
+
+            // FIFOs, memfds and shm files are told apart by what is behind the link
+            let files = info
+                .fds
+                .iter()
+                .filter(|entry| entry.link.starts_with('/'))
+                .filter_map(|entry| Some((entry.fd, process.stat(&format!("fd/{}", entry.fd)).ok()?)))
+                .collect();
+            let shared_maps = match data.get_maps(process) {
+                Ok(entries) => entries
+                    .into_iter()
+                    .filter(|entry| entry.path.as_deref().is_some_and(is_shared_memory))
+                    .collect(),
+                Err(err) if Self::is_gone(&err) => return Err(Error::ProcessVanished { pid: pid.to_string() }),
+                Err(err) => {
+                    diagnostics.unreadable(format!("/proc/{}/maps", pid), &err);
+                    Vec::new()
+                }
+            };
+
+            Ok(IpcProcess {
+                info,
+                ppid,
+                cgroup: process.read_to_string("cgroup").ok().as_deref().and_then(cgroup_path),
+                netns,
+                files,
+                shared_maps,
+            })
+        })?;
+        Ok(IpcScan { processes, tables })
+    }
+
+    /// Get every pipe and FIFO with the descriptors referring to it
+    ///
+    /// Each end is labelled reader or writer from the flags in
+    /// `/proc/<pid>/fdinfo/<fd>`. FIFOs are recognised by file type, so
+    /// their descriptors are stat'ed through `/proc/<pid>/fd`.
+    pub fn pipe_ls(&mut self) -> Result<Vec<Pipe>> {
+        Ok(pipes(&self.ipc_scan()?))
+    }
+
+    /// Get the other ends of the pipe behind descriptor `fd` of process `pid`
+    ///
+    /// For example, `pipe_peers("1234", 1)` answers what the standard output
+    /// of process 1234 is piped to. Returns an empty list if the descriptor
+    /// is not a pipe or FIFO.
+    pub fn pipe_peers(&mut self, pid: &str, fd: u32) -> Result<Vec<PipeEnd>> {
+        let pipe = self
+            .pipe_ls()?
+            .into_iter()
+            .find(|pipe| pipe.ends.iter().any(|end| end.pid == pid && end.fd == fd));
+        Ok(pipe
+            .map(|pipe| pipe.ends.into_iter().filter(|end| end.pid != pid || end.fd != fd).collect())
+            .unwrap_or_default())
+    }
+
+    /// Get every unix socket held by a process, with the processes at the
+    /// other end of its connection
+    ///
+    /// Peers come from the `sock_diag` netlink interface. Where that is not
+    /// available, sockets are matched to `/proc/net/unix` by inode for their
+    /// path and type, `peers` is `None`, and the diagnostics list
+    /// [`Limitation::SockDiagUnavailable`]. Only sockets in the caller's
+    /// network namespace are reported.
+    pub fn unix_ls(&mut self) -> Result<Vec<UnixConnection>> {
+        let scan = self.ipc_scan()?;
+        let (sockets, peers_known) = unix_sockets(&mut self.diagnostics)?;
+        Ok(unix_connections(&scan, sockets, peers_known))
+    }
+
+    /// Get the processes at the other end of the unix socket behind
+    /// descriptor `fd` of process `pid`
+    ///
+    /// Returns an empty list if the descriptor is not a connected unix
+    /// socket, and `None` if peers are unknown because `sock_diag` is not
+    /// available.
+    pub fn unix_peers(&mut self, pid: &str, fd: u32) -> Result<Option<Vec<SocketEnd>>> {
+        let connection = self
+            .unix_ls()?
+            .into_iter()
+            .find(|connection| connection.holders.iter().any(|end| end.pid == pid && end.fd == fd));
+        Ok(connection.map_or(Some(Vec::new()), |connection| connection.peers))
+    }
+
+    /// Get every TCP and UDP socket held by a process, with the process at
+    /// the other end when the connection is local
+    ///
+    /// A connection is local when its remote endpoint is a socket in the same
+    /// network namespace, as with connections over 127.0.0.1 or ::1. A
+    /// connected UDP socket whose remote end is not connected back is matched
+    /// to the socket bound to its remote address, or to a wildcard bind on
+    /// that port.
+    pub fn inet_ls(&mut self) -> Result<Vec<InetConnection>> {
+        Ok(inet_connections(&self.ipc_scan()?))
+    }
+
+    /// Get the processes at the other end of the TCP or UDP socket behind
+    /// descriptor `fd` of process `pid`
+    ///
+    /// Returns an empty list if the descriptor is not a socket connected to
+    /// a local one.
+    pub fn inet_peers(&mut self, pid: &str, fd: u32) -> Result<Vec<SocketEnd>> {
+        let connection = self
+            .inet_ls()?
+            .into_iter()
+            .find(|connection| connection.holders.iter().any(|end| end.pid == pid && end.fd == fd));
+        Ok(connection.map(|connection| connection.peers).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_pipe_roles() {
+        assert_eq!(bracket_inode("pipe:[4242]", "pipe:["), Some(4242));
+        assert_eq!(bracket_inode("socket:[4242]", "pipe:["), None);
+        assert_eq!(PipeRole::from_flags(0o2000000), Some(PipeRole::Reader));
+        assert_eq!(PipeRole::from_flags(0o2000001), Some(PipeRole::Writer));
+        assert_eq!(PipeRole::from_flags(0o2), Some(PipeRole::ReadWrite));
+        assert!(PipeRole::ReadWrite.reads() && PipeRole::ReadWrite.writes());
+    }
+
+    #[test]
+    fn test_pipe_peers() {
+        // The child's stdout is a pipe the test reads from
+        let mut child = Command::new("sleep").arg("5").stdout(Stdio::piped()).spawn().unwrap();
+        let child_pid = child.id().to_string();
+
+        let mut d = LsofData::new();
+        let peers = d.pipe_peers(&child_pid, 1).unwrap();
+        let me = std::process::id().to_string();
+        let reader = peers.iter().find(|end| end.pid == me).expect("reading end not found");
+        assert_eq!(reader.role, Some(PipeRole::Reader));
+
+        let pipes = d.pipe_ls().unwrap();
+        let pipe = pipes.iter().find(|p| p.ends.iter().any(|e| e.pid == child_pid && e.fd == 1)).unwrap();
+        assert!(pipe.writers().any(|end| end.pid == child_pid));
+        assert!(pipe.fifo.is_none());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_parse_net_unix() {
+        let content = "\
+Num       RefCount Protocol Flags    Type St Inode Path
+0000000000000000: 00000002 00000000 00010000 0001 01 24377 /run/my app.sock
+0000000000000000: 00000003 00000000 00000000 0002 03 24380 @abstract
+0000000000000000: 00000003 00000000 00000000 0005 03 24381";
+        let sockets = parse_net_unix(content);
+        assert_eq!(sockets.len(), 3);
+        assert!(sockets[0].listening);
+        assert_eq!(sockets[0].path.as_deref(), Some("/run/my app.sock"));
+        assert_eq!(sockets[1].socket_type, UnixSocketType::Datagram);
+        assert_eq!(sockets[1].path.as_deref(), Some("@abstract"));
+        assert_eq!(sockets[2].socket_type, UnixSocketType::SeqPacket);
+        assert_eq!(sockets[2].path, None);
+
+        assert_eq!(unix_path(b"\0hidden"), "@hidden");
+        assert_eq!(unix_path(b"/run/a.sock\0\0"), "/run/a.sock");
+    }
+
+    #[test]
+    fn test_unix_peers() {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let (a, b) = UnixStream::pair().unwrap();
+        let me = std::process::id().to_string();
+        let mut d = LsofData::new();
+        let connections = d.unix_ls().unwrap();
+        assert!(connections.iter().any(|c| c.holders.iter().any(|e| e.pid == me && e.fd == a.as_raw_fd() as u32)));
+
+        // Peers are only known with sock_diag
+        let peers = d.unix_peers(&me, a.as_raw_fd() as u32).unwrap();
+        let unknown = d.diagnostics().limitations.contains(&Limitation::SockDiagUnavailable);
+        match peers {
+            Some(peers) => assert!(peers.iter().any(|end| end.pid == me && end.fd == b.as_raw_fd() as u32)),
+            None => assert!(unknown),
+        }
+        assert_eq!(unknown, sockdiag::unix_sockets().is_err());
+
+
+        // From the /proc/net/unix fallback, peers are unknown rather than absent
+        let scan = d.ipc_scan().unwrap();
+        let sockets = parse_net_unix(&read_to_string("/proc/net/unix").unwrap());
+        let connections = unix_connections(&scan, sockets, false);
+        assert!(connections.iter().any(|c| c.holders.iter().any(|e| e.pid == me && e.fd == a.as_raw_fd() as u32)));
+        assert!(connections.iter().all(|c| c.peers.is_none()));
+    }
+
+    #[test]
+    fn test_inet_peers() {
+        use std::net::{TcpListener, TcpStream};
+        use std::os::fd::AsRawFd;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let me = std::process::id().to_string();
+        let mut d = LsofData::new();
+        let peers = d.inet_peers(&me, client.as_raw_fd() as u32).unwrap();
+        assert!(peers.iter().any(|end| end.pid == me && end.fd == server.as_raw_fd() as u32));
+
+        let connections = d.inet_ls().unwrap();
+        let listening = connections
+            .iter()
+            .find(|c| c.holders.iter().any(|e| e.pid == me && e.fd == listener.as_raw_fd() as u32))
+            .unwrap();
+        assert_eq!(listening.socket.state, SocketState::Listen);
+        assert!(listening.peer.is_none());
+    }
+
+    #[test]
+    fn test_udp_peers() {
+        use std::net::UdpSocket;
+        use std::os::fd::AsRawFd;
+
+        let me = std::process::id().to_string();
+        let mut d = LsofData::new();
+        for bind in ["127.0.0.1:0", "0.0.0.0:0"] {
+            let server = UdpSocket::bind(bind).unwrap();
+            let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+            client.connect(("127.0.0.1", server.local_addr().unwrap().port())).unwrap();
+
+            let peers = d.inet_peers(&me, client.as_raw_fd() as u32).unwrap();
+            assert!(peers.iter().any(|end| end.pid == me && end.fd == server.as_raw_fd() as u32), "server bound to {}", bind);
+            // The unconnected server has no single peer
+            assert!(d.inet_peers(&me, server.as_raw_fd() as u32).unwrap().is_empty());
+        }
+    }
+}
@@ -1,8 +1,12 @@
 // #![doc = include_str!("../README.md")]
 use glob::glob;
+use procfs::ProcessHandle;
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, read_to_string};
-use std::path::Path;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -15,9 +19,43 @@ pub enum Error {
     
     #[error("Glob pattern error: {0}")]
     Glob(#[from] glob::PatternError),
-    
-    #[error("Other error: {0}")]
-    Other(String),
+
+    #[error("Target not found: {}", .0.display())]
+    TargetNotFound(PathBuf),
+
+    #[error("Permission denied reading process {pid}")]
+    PermissionDenied { pid: String },
+
+    #[error("Process {pid} exited while being read")]
+    ProcessVanished { pid: String },
+
+    #[error("/proc is not mounted or not readable")]
+    ProcfsUnavailable,
+
+    #[error("Invalid port: {0}")]
+    InvalidPort(String),
+
+    #[error("Invalid process ID: {0}")]
+    InvalidPid(String),
+
+    #[error("Descriptor {fd} of process {pid} no longer refers to the reported file")]
+    InodeMismatch { pid: String, fd: u32 },
+
+    #[error("File behind descriptor {fd} of process {pid} is no longer deleted")]
+    NotDeleted { pid: String, fd: u32 },
+
+    #[error("Refusing to modify a file without confirmation")]
+    NotConfirmed,
+
+    #[error("File behind descriptor {fd} of process {pid} is mapped into memory by {}", .mappers.join(", "))]
+    FileMapped { pid: String, fd: u32, mappers: Vec<String> },
+
+    #[error("Results would be incomplete: {}", .0.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(", "))]
+    IncompleteVisibility(Vec<probe::Limitation>),
+
+    #[cfg(feature = "async")]
+    #[error("Background task failed: {0}")]
+    TaskFailed(#[from] tokio::task::JoinError),
 }
 
 // The filesync module is always available, regardless of features
@@ -26,6 +64,46 @@ pub mod filesync;
 #[cfg(feature = "async")]
 pub mod fileasync;
 
+pub mod snapshot;
+
+pub mod diff;
+
+pub mod watch;
+
+pub mod mountinfo;
+
+pub mod probe;
+
+mod procfs;
+
+pub mod deleted;
+
+pub mod maps;
+
+pub mod stale;
+
+pub mod library;
+
+pub mod memory;
+
+pub mod audit;
+
+pub mod locks;
+
+pub mod ipc;
+
+mod sockdiag;
+
+pub mod net;
+
+pub mod graph;
+
+pub mod fdinfo;
+
+pub mod notify;
+
+pub mod namespace;
+
 /// Represents information about a file descriptor
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Fdinfo {
@@ -35,6 +113,89 @@ pub struct Fdinfo {
     pub name: Option<String>,
     /// Set of links associated with this file descriptor
     pub link: HashSet<String>,
+    /// Process start time in clock ticks since boot (if available)
+    pub start_time: Option<u64>,
+    /// Open file descriptors, sorted by descriptor number
+    pub fds: Vec<FdEntry>,
+}
+
+/// A single open file descriptor of a process
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FdEntry {
+    /// Descriptor number
+    pub fd: u32,
+    /// Target of the `/proc/<pid>/fd/<fd>` link
+    pub link: String,
+    /// File offset, from `/proc/<pid>/fdinfo/<fd>` (if available)
+    pub pos: Option<u64>,
+    /// Open flags such as `O_WRONLY`, from `/proc/<pid>/fdinfo/<fd>` (if available)
+    pub flags: Option<u32>,
+    /// Decoded fdinfo of anonymous inodes such as eventfds and epoll instances
+    pub detail: Option<fdinfo::FdDetail>,
+}
+
+/// Parts of a scan that could not be read
+///
+/// Scans leave out what they cannot read instead of failing, so an empty
+/// result only means "nobody uses this" when the diagnostics are complete.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+    /// Processes skipped because reading them was not permitted
+    pub permission_denied: Vec<String>,
+    /// Processes that exited while they were being read
+    pub vanished: Vec<String>,
+    /// Files that could not be read, and why
+    pub unreadable: Vec<Unreadable>,
+    /// Known reasons, found by probing, why processes may be missing entirely
+    pub limitations: Vec<probe::Limitation>,
+}
+
+/// A file under `/proc` that a scan could not read
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unreadable {
+    /// Path that failed
+    pub path: PathBuf,
+    /// Kind of failure
+    pub kind: io::ErrorKind,
+}
+
+impl Diagnostics {
+    /// Whether the scan saw every process and file it looked for
+    pub fn is_complete(&self) -> bool {
+        self.permission_denied.is_empty() && self.unreadable.is_empty() && self.limitations.is_empty()
+    }
+
+    /// Record a file that could not be read
+    fn unreadable(&mut self, path: impl Into<PathBuf>, err: &io::Error) {
+        self.unreadable.push(Unreadable {
+            path: path.into(),
+            kind: err.kind(),
+        });
+    }
+
+    /// Record why a process was left out of a scan
+    fn skipped(&mut self, pid: &str, err: Error) {
+        match err {
+            Error::PermissionDenied { pid } => self.permission_denied.push(pid),
+            Error::ProcessVanished { pid } => self.vanished.push(pid),
+            Error::Io(err) => self.unreadable(format!("/proc/{}", pid), &err),
+            _ => self.unreadable.push(Unreadable {
+                path: PathBuf::from(format!("/proc/{}", pid)),
+                kind: io::ErrorKind::Other,
+            }),
+        }
+    }
+}
+
+/// Result of a full scan, with the diagnostics collected along the way
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    /// Map of process IDs to their file descriptor info
+    pub processes: HashMap<String, Fdinfo>,
+    /// Parts of the scan that could not be read
+    pub diagnostics: Diagnostics,
+    /// Time at which the scan was collected
+    pub scanned_at: SystemTime,
 }
 
 /// Type of file to look for
@@ -58,6 +219,36 @@ pub struct LsofData {
     targetmap: HashMap<String, HashSet<String>>,
     /// Target file name to search for
     target_filename: String,
+    /// Time at which the current `pidmap` was collected
+    scanned_at: Option<SystemTime>,
+    /// Parts of the current scan that could not be read
+    diagnostics: Diagnostics,
+    /// Limitations reported in the diagnostics of every scan
+    limitations: Vec<probe::Limitation>,
+}
+
+/// Trait defining file information retrieval methods
+pub trait GetFileInfo {
+    /// Get process information from a status file such as `/proc/<pid>/status`
+    fn get_pid_info(&self, path: String) -> Result<HashMap<String, String>>;
+    /// Get the mapped paths from a maps file such as `/proc/<pid>/maps`
+    fn get_mem_info(&self, path: String) -> Result<Vec<String>>;
+    /// Get the sockets bound to a port, as `socket:[inode]`
+    fn get_port_info(&self, port: &str) -> Result<Vec<String>>;
+}
+
+impl GetFileInfo for LsofData {
+    fn get_pid_info(&self, path: String) -> Result<HashMap<String, String>> {
+        Ok(Self::parse_status(&read_to_string(path)?))
+    }
+
+    fn get_mem_info(&self, path: String) -> Result<Vec<String>> {
+        Ok(maps::parse(&read_to_string(path)?).into_iter().filter_map(|entry| entry.path).collect())
+    }
+
+    fn get_port_info(&self, port: &str) -> Result<Vec<String>> {
+        Ok(LsofData::get_port_info(self, Self::parse_port(port)?))
+    }
 }
 
 impl Default for LsofData {
@@ -66,16 +257,6 @@ impl Default for LsofData {
     }
 }
 
-/// Trait defining file information retrieval methods
-pub trait GetFileInfo {
-    /// Get process information from a path
-    fn get_pid_info(&self, path: String) -> HashMap<String, String>;
-    /// Get memory-mapped file information
-    fn get_mem_info(&self, path: String) -> Vec<String>;
-    /// Get information about a socket port
-    fn get_port_info(&self, port: &str) -> Option<Vec<String>>;
-}
-
 impl LsofData {
     /// Create a new LsofData instance
     pub fn new() -> LsofData {
@@ -84,106 +265,85 @@ impl LsofData {
             pidmap: HashMap::new(),
             targetmap: HashMap::new(),
             target_filename: String::new(),
+            scanned_at: None,
+            diagnostics: Diagnostics::default(),
+            limitations: Vec::new(),
+        }
+    }
+
+    /// Time at which the results of the last query were collected
+    ///
+    /// Every query rescans `/proc` from scratch, so this is the age of the
+    /// data returned by the most recent call, or `None` before the first one.
+    pub fn scanned_at(&self) -> Option<SystemTime> {
+        self.scanned_at
+    }
+
+    /// Parts of the last query's scan that could not be read
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Drop everything collected by a previous scan
+    fn reset(&mut self) {
+        self.pidmap.clear();
+        self.targetmap.clear();
+        self.scanned_at = None;
+        self.diagnostics = self.new_diagnostics();
+    }
+
+    /// Diagnostics for a new scan, seeded with the known limitations
+    fn new_diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            limitations: self.limitations.clone(),
+            ..Default::default()
         }
     }
 
-    /// Parse and return process information from a file
-    fn get_pid_info(&self, path: String) -> HashMap<String, String> {
+    /// Parse and return process information from `/proc/<pid>/status`
+    fn get_pid_info(&self, process: &ProcessHandle, pid: &str) -> Result<HashMap<String, String>> {
+        let content = process.read_to_string("status").map_err(|err| Self::pid_error(pid, err))?;
+        Ok(Self::parse_status(&content))
+    }
+
+    /// Parse the `key: value` lines of a status file
+    fn parse_status(content: &str) -> HashMap<String, String> {
         let mut map: HashMap<String, String> = HashMap::new();
-        match read_to_string(path) {
-            Ok(content) => {
-                for line in content.lines() {
-                    if let Some((key, value)) = line.split_once(':') {
-                        map.insert(key.trim().to_string(), value.trim().to_string());
-                    }
-                }
-            }
-            Err(_) => {
-                // Return empty map if file cannot be read
+        for line in content.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                map.insert(key.trim().to_string(), value.trim().to_string());
             }
         }
         map
     }
 
-    /// Get memory-mapped file information from a file
-    fn get_mem_info(&self, path: String) -> Vec<String> {
-        let mut datas: Vec<String> = Vec::new();
-        match read_to_string(path) {
-            Ok(content) => {
-                for line in content.lines() {
-                    let v: Vec<&str> = line.split_whitespace().collect();
-                    if let Some(last_part) = v.last() {
-                        if !last_part.is_empty() && v.len() >= 6 {
-                            datas.push(last_part.to_string());
-                        }
-                    }
-                }
-            }
-            Err(_) => {
-                // Return empty vector if file cannot be read
-            }
-        }
-        datas
+    /// Parse the memory mappings of a process from `/proc/<pid>/maps`
+    fn get_maps(&self, process: &ProcessHandle) -> io::Result<Vec<maps::MapEntry>> {
+        Ok(maps::parse(&process.read_to_string("maps")?))
+    }
+
+    /// Get memory-mapped file information from `/proc/<pid>/maps`
+    fn get_mem_info(&self, process: &ProcessHandle) -> io::Result<Vec<String>> {
+        Ok(self.get_maps(process)?.into_iter().filter_map(|entry| entry.path).collect())
     }
 
     /// Get socket information for a specific port
-    fn get_port_info(&self, port: &str) -> Option<Vec<String>> {
+    fn get_port_info(&self, port: u16) -> Vec<String> {
         let mut socket_files = Vec::new();
-        
-        // Check TCP sockets
-        if let Ok(tcp_content) = read_to_string("/proc/net/tcp") {
-            socket_files.extend(self.parse_socket_file(tcp_content, port));
-        }
-        
-        // Check TCP6 sockets
-        if let Ok(tcp6_content) = read_to_string("/proc/net/tcp6") {
-            socket_files.extend(self.parse_socket_file(tcp6_content, port));
-        }
-        
-        // Check UDP sockets
-        if let Ok(udp_content) = read_to_string("/proc/net/udp") {
-            socket_files.extend(self.parse_socket_file(udp_content, port));
-        }
-        
-        // Check UDP6 sockets
-        if let Ok(udp6_content) = read_to_string("/proc/net/udp6") {
-            socket_files.extend(self.parse_socket_file(udp6_content, port));
-        }
-        
-        if socket_files.is_empty() {
-            None
-        } else {
-            Some(socket_files)
-        }
-    }
-    
-    /// Parse socket file content looking for a specific port
-    fn parse_socket_file(&self, content: String, port: &str) -> Vec<String> {
-        let mut results = Vec::new();
-        
-        for line in content.lines().skip(1) { // Skip header line
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 10 {
-                continue;
-            }
-            
-            // Local address is in column 1, format is IP:PORT in hex
-            let local_addr = parts[1];
-            if let Some(colon_pos) = local_addr.rfind(':') {
-                let port_hex = &local_addr[colon_pos+1..];
-                // Convert hex port to decimal
-                if let Ok(port_num) = u16::from_str_radix(port_hex, 16) {
-                    if port_num.to_string() == port {
-                        // Column 9 contains inode number
-                        if let Some(inode) = parts.get(9) {
-                            results.push(format!("socket:[{}]", inode));
-                        }
-                    }
-                }
+
+        // Check TCP, TCP6, UDP and UDP6 sockets
+        for protocol in net::Protocol::ALL {
+            if let Ok(content) = read_to_string(format!("/proc/net/{}", protocol.file_name())) {
+                socket_files.extend(
+                    net::parse(&content, protocol)
+                        .into_iter()
+                        .filter(|socket| socket.local.port() == port)
+                        .map(|socket| format!("socket:[{}]", socket.inode)),
+                );
             }
         }
-        
-        results
+
+        socket_files
     }
 
     /// Insert a PID into the target map
@@ -197,161 +357,295 @@ impl LsofData {
         }
     }
 
-    /// List all files across processes
-    fn set_list_all(&mut self) -> Result<()> {
+    /// Get the IDs of all processes currently visible in /proc
+    fn proc_pids(&self, diagnostics: &mut Diagnostics) -> Result<Vec<String>> {
+        if fs::metadata("/proc/self").is_err() {
+            return Err(Error::ProcfsUnavailable);
+        }
+
+        let mut pids = Vec::new();
         let proc_paths = glob("/proc/*").map_err(Error::Glob)?;
-        
+
         for proc_path_r in proc_paths {
             match proc_path_r {
                 Ok(proc_path) => {
                     // Get the process ID from the path
-                    let pid_os_str = proc_path.file_name()
-                        .ok_or_else(|| Error::Other("Failed to get process directory name".to_string()))?;
+                    let pid_os_str = proc_path.file_name().ok_or(Error::ProcfsUnavailable)?;
                     let pid = pid_os_str.to_string_lossy();
-                    
+
                     // Skip non-numeric (non-process) directories
                     if pid.parse::<u64>().is_err() {
                         continue;
                     }
+                    pids.push(pid.to_string());
+                }
+                Err(e) => {
+                    // Record the error and continue
+                    diagnostics.unreadable(e.path(), e.error());
+                }
+            }
+        }
+        Ok(pids)
+    }
 
-                    let fd_path_str = format!("{}/fd/*", proc_path.display());
-
-                    let mut info = Fdinfo::default();
-                    info.pid = pid.to_string();
-                    
-                    // Get process information
-                    let other_info = self.get_pid_info(format!("/proc/{}/status", pid));
-                    info.name = other_info.get("Name").cloned();
-
-                    // Get process memory mapping information
-                    if let Some(filetype) = &self.target_filetype {
-                        if *filetype == LsofFiletype::Mem || *filetype == LsofFiletype::All {
-                            let mem_info = self.get_mem_info(format!("/proc/{}/maps", pid));
-                            for i in mem_info {
-                                info.link.insert(i.clone());
-                                if !self.target_filename.is_empty() && self.target_filename == i {
-                                    self.target_map_insert(pid.to_string());
-                                }
-                            }
-                        }
+    /// Get the start time, in clock ticks since boot, from the contents of
+    /// `/proc/<pid>/stat`
+    ///
+    /// Together with the pid this identifies a process instance, since pids
+    /// are recycled but start times are not.
+    fn parse_start_time(stat: &str) -> Option<u64> {
+        // The command name may contain spaces, so split after its closing paren
+        let (_, rest) = stat.rsplit_once(')')?;
+        // starttime is field 22; `rest` starts at field 3
+        rest.split_whitespace().nth(19)?.parse().ok()
+    }
+
+    /// Get the offset and open flags from the contents of `/proc/<pid>/fdinfo/<fd>`
+    fn parse_fdinfo(content: &str) -> (Option<u64>, Option<u32>) {
+        let mut pos = None;
+        let mut flags = None;
+        for line in content.lines() {
+            if let Some(value) = line.strip_prefix("pos:") {
+                pos = value.trim().parse().ok();
+            } else if let Some(value) = line.strip_prefix("flags:") {
+                // Flags are printed in octal
+                flags = u32::from_str_radix(value.trim(), 8).ok();
+            }
+        }
+        (pos, flags)
+    }
+
+    /// Whether an error from reading `/proc/<pid>` means the process exited
+    fn is_gone(err: &io::Error) -> bool {
+        err.kind() == io::ErrorKind::NotFound || err.raw_os_error() == Some(libc::ESRCH)
+    }
+
+    /// Map an error from reading `/proc/<pid>` to what it says about the process
+    fn pid_error(pid: &str, err: io::Error) -> Error {
+        if Self::is_gone(&err) {
+            return Error::ProcessVanished { pid: pid.to_string() };
+        }
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied { pid: pid.to_string() },
+            _ => Error::Io(err),
+        }
+    }
+
+    /// Collect the open files of a single process
+    ///
+    /// The process is opened once and every file is read relative to that
+    /// handle, so a pid reused mid-scan fails the scan instead of mixing two
+    /// processes into one record. Files of the process that cannot be read
+    /// are recorded in `diagnostics`.
+    fn scan_pid(&self, pid: &str, diagnostics: &mut Diagnostics) -> Result<Fdinfo> {
+        Self::read_process(pid, |process| self.scan_process(pid, process, diagnostics))
+    }
+
+    /// Open process `pid` and read it with `read`
+    ///
+    /// Fails with `ProcessVanished` if the process exited before `read`
+    /// returned, since what was read may then be incomplete.
+    fn read_process<T>(pid: &str, read: impl FnOnce(&ProcessHandle) -> Result<T>) -> Result<T> {
+        let process = ProcessHandle::open(pid).map_err(|err| Self::pid_error(pid, err))?;
+        let value = read(&process)?;
+
+        // Everything above must belong to the process that was opened
+        if !process.is_alive() {
+            return Err(Error::ProcessVanished { pid: pid.to_string() });
+        }
+        Ok(value)
+    }
+
+    /// Collect the open files of an opened process
+    fn scan_process(&self, pid: &str, process: &ProcessHandle, diagnostics: &mut Diagnostics) -> Result<Fdinfo> {
+        let proc_path = |name: &str| PathBuf::from(format!("/proc/{}/{}", pid, name));
+
+        let mut info = Fdinfo {
+            pid: pid.to_string(),
+            name: self.get_pid_info(process, pid)?.remove("Name"),
+            start_time: process.read_to_string("stat").ok().as_deref().and_then(Self::parse_start_time),
+            ..Default::default()
+        };
+
+        // Get process memory mapping information
+        if let Some(filetype) = &self.target_filetype {
+            if *filetype == LsofFiletype::Mem || *filetype == LsofFiletype::All {
+                match self.get_mem_info(process) {
+                    Ok(mem_info) => info.link.extend(mem_info),
+                    Err(err) if Self::is_gone(&err) => {
+                        return Err(Error::ProcessVanished { pid: pid.to_string() });
                     }
+                    Err(err) => diagnostics.unreadable(proc_path("maps"), &err),
+                }
+            }
+        }
 
-                    // Get file descriptor information
-                    if let Ok(fd_paths) = glob(&fd_path_str) {
-                        for fd_path in fd_paths {
-                            match fd_path {
-                                Ok(path_data) => {
-                                    // Get the symbolic link target
-                                    if let Ok(link) = fs::read_link(&path_data) {
-                                        let link_str = link.to_string_lossy().to_string();
-                                        
-                                        // Store inode information if it's a socket
-                                        let is_socket = link_str.starts_with("socket:[");
-                                        
-                                        info.link.insert(link_str.clone());
-                                        
-                                        if !self.target_filename.is_empty() && self.target_filename == link_str {
-                                            self.target_map_insert(pid.to_string());
-                                        }
-                                        
-                                        // Handle socket file type if needed
-                                        if let Some(filetype) = &self.target_filetype {
-                                            if *filetype == LsofFiletype::Socket && is_socket {
-                                                // Already handled by adding to info.link above
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    // Log error and continue
-                                    eprintln!("Error accessing file descriptor: {}", e);
-                                }
-                            }
+        // Get file descriptor information, leaving out the handle's own when scanning ourselves
+        let own_fds = process.own_fds();
+        let fd_names = process.read_dir("fd").map_err(|err| Self::pid_error(pid, err))?;
+        for name in fd_names {
+            let Some(fd) = name.to_str().and_then(|n| n.parse::<u32>().ok()) else {
+                continue;
+            };
+            if own_fds.contains(&fd) {
+                continue;
+            }
+            // Get the symbolic link target; the fd may close while we look
+            let fd_name = format!("fd/{}", fd);
+            match process.read_link(&fd_name) {
+                Ok(link) => {
+                    let link_str = link.to_string_lossy().to_string();
+                    info.link.insert(link_str.clone());
+                    // The fd may close before its fdinfo is read; keep the link anyway
+                    let (pos, flags, detail) = match process.read_to_string(&format!("fdinfo/{}", fd)) {
+                        Ok(content) => {
+                            let (pos, flags) = Self::parse_fdinfo(&content);
+                            (pos, flags, fdinfo::parse(&link_str, &content))
                         }
-                        self.pidmap.insert(pid.to_string(), info);
-                    }
+                        Err(_) => (None, None, None),
+                    };
+                    info.fds.push(FdEntry { fd, link: link_str, pos, flags, detail });
                 }
-                Err(e) => {
-                    // Log error and continue
-                    eprintln!("Error processing process directory: {}", e);
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) if Self::is_gone(&err) => {
+                    return Err(Error::ProcessVanished { pid: pid.to_string() });
                 }
+                Err(err) => diagnostics.unreadable(proc_path(&fd_name), &err),
             }
         }
+        info.fds.sort_by_key(|entry| entry.fd);
+        Ok(info)
+    }
+
+    /// Open every process visible in /proc and collect what `visit` returns
+    ///
+    /// Starts a new scan. A process that errors, or exits before `visit`
+    /// returns, is left out and recorded in the diagnostics, so results never
+    /// mix two processes that shared a pid.
+    fn visit_processes<T, F>(&mut self, mut visit: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&Self, &str, &ProcessHandle, &mut Diagnostics) -> Result<T>,
+    {
+        self.reset();
+        self.scanned_at = Some(SystemTime::now());
+
+        let mut diagnostics = self.new_diagnostics();
+        let mut results = Vec::new();
+        for pid in self.proc_pids(&mut diagnostics)? {
+            match Self::read_process(&pid, |process| visit(self, &pid, process, &mut diagnostics)) {
+                Ok(value) => results.push(value),
+                Err(err) => diagnostics.skipped(&pid, err),
+            }
+        }
+        self.diagnostics = diagnostics;
+        Ok(results)
+    }
+
+    /// List all files across processes
+    fn set_list_all(&mut self) -> Result<()> {
+        // Processes that exited or cannot be read are left out
+        let infos = self.visit_processes(|data, pid, process, diagnostics| {
+            data.scan_process(pid, process, diagnostics)
+        })?;
+        for info in infos {
+            if !self.target_filename.is_empty() && info.link.contains(&self.target_filename) {
+                self.target_map_insert(info.pid.clone());
+            }
+            self.pidmap.insert(info.pid.clone(), info);
+        }
         Ok(())
     }
 
     /// Get information about processes using a specific file
-    pub fn target_file_ls(&mut self, path: String) -> Option<Vec<Fdinfo>> {
-        let mut result: Vec<Fdinfo> = Vec::new();
-        
+    pub fn target_file_ls(&mut self, path: String) -> Result<Vec<Fdinfo>> {
         // Check if the file exists
         let path = Path::new(&path);
         if !path.exists() {
-            return None;
+            return Err(Error::TargetNotFound(path.to_path_buf()));
         }
 
         self.target_filetype = Some(LsofFiletype::All);
         self.target_filename = path.to_string_lossy().to_string();
         
-        if self.set_list_all().is_err() {
-            return None;
-        }
+        self.set_list_all()?;
 
         // Get processes using the target file
-        if let Some(pids) = self.targetmap.get(&self.target_filename) {
-            for pid in pids {
-                if let Some(info) = self.pidmap.get(pid) {
-                    result.push(info.clone());
-                }
-            }
-            Some(result)
-        } else {
-            None
-        }
+        let pids = self.targetmap.get(&self.target_filename).into_iter().flatten();
+        Ok(pids.filter_map(|pid| self.pidmap.get(pid)).cloned().collect())
     }
 
     /// Get information about all open files by all processes
-    pub fn file_ls(&mut self) -> Option<&HashMap<String, Fdinfo>> {
+    pub fn file_ls(&mut self) -> Result<&HashMap<String, Fdinfo>> {
         self.target_filetype = Some(LsofFiletype::All);
         
-        if self.set_list_all().is_err() {
-            return None;
-        }
+        self.set_list_all()?;
         
-        Some(&self.pidmap)
+        Ok(&self.pidmap)
+    }
+
+    /// Scan all processes and return their open files with diagnostics
+    pub fn scan(&mut self) -> Result<Scan> {
+        self.file_ls()?;
+
+        Ok(Scan {
+            processes: mem::take(&mut self.pidmap),
+            diagnostics: self.diagnostics.clone(),
+            scanned_at: self.scanned_at.unwrap_or_else(SystemTime::now),
+        })
+    }
+
+    /// Parse a port number given as a string
+    fn parse_port(port: &str) -> Result<u16> {
+        port.trim().parse().map_err(|_| Error::InvalidPort(port.to_string()))
+    }
+
+    /// Parse a process ID given as a string
+    fn parse_pid(pid: &str) -> Result<u32> {
+        pid.trim().parse().map_err(|_| Error::InvalidPid(pid.to_string()))
     }
 
     /// Get information about processes using a specific port
-    pub fn port_ls(&mut self, port: String) -> Option<Vec<Fdinfo>> {
-        let mut result: Vec<Fdinfo> = Vec::new();
-        
+    pub fn port_ls(&mut self, port: String) -> Result<Vec<Fdinfo>> {
+        let port = Self::parse_port(&port)?;
         self.target_filetype = Some(LsofFiletype::Socket);
-        
+        self.target_filename = String::new();
+
         // Get socket inodes for the port
-        let socket_inodes = self.get_port_info(&port)?;
-        
-        // For each socket inode, find the corresponding processes
-        for inode in socket_inodes {
-            self.target_filename = inode;
-            
-            if self.set_list_all().is_err() {
-                continue;
-            }
-            
-            if let Some(pids) = self.targetmap.get(&self.target_filename) {
-                for pid in pids {
-                    if let Some(info) = self.pidmap.get(pid) {
-                        result.push(info.clone());
-                    }
-                }
-            }
+        let socket_inodes: HashSet<String> = self.get_port_info(port).into_iter().collect();
+        if socket_inodes.is_empty() {
+            self.reset();
+            return Ok(Vec::new());
         }
-        
-        if result.is_empty() {
-            None
-        } else {
-            Some(result)
+
+        // A single scan covers every inode bound to the port
+        self.set_list_all()?;
+
+        Ok(self
+            .pidmap
+            .values()
+            .filter(|info| !info.link.is_disjoint(&socket_inodes))
+            .cloned()
+            .collect())
+    }
+
+    /// Get information about the open files of a single process
+    ///
+    /// Unlike the scans above, which leave out processes they cannot read,
+    /// this reports why the process could not be read. Paths of inotify
+    /// watches and fanotify marks are resolved, which scans skip.
+    pub fn process_ls(&mut self, pid: String) -> Result<Fdinfo> {
+        self.reset();
+        self.target_filetype = Some(LsofFiletype::All);
+        self.scanned_at = Some(SystemTime::now());
+
+        let mut diagnostics = self.new_diagnostics();
+        let mut info = self.scan_pid(&pid, &mut diagnostics);
+        self.diagnostics = diagnostics;
+        // Only worth it for a single process; see `fdinfo::resolve_paths`
+        if let Ok(info) = &mut info {
+            fdinfo::resolve_paths(&mut info.fds);
         }
+        info
     }
 }
 
@@ -359,7 +653,7 @@ impl LsofData {
 fn test_lsall() {
     let mut d = LsofData::new();
     let result = d.file_ls();
-    assert_ne!(result, None);
+    assert!(result.is_ok());
 }
 
 #[test]
@@ -367,14 +661,91 @@ fn test_target_file() {
     let filepath = "/etc/passwd".to_string();
     let mut d = LsofData::new();
     let result = d.target_file_ls(filepath);
-    assert!(result.is_some() || result.is_none()); // Either result is acceptable for the test
+    assert!(result.is_ok()); // An empty list is acceptable for the test
+
+    let result = d.target_file_ls("/nonexistent/minilsof".to_string());
+    assert!(matches!(result, Err(Error::TargetNotFound(_))));
 }
 
 #[test]
 fn test_port() {
     let mut d = LsofData::new();
     let result = d.port_ls("80".to_string());
-    assert!(result.is_some() || result.is_none()); // Either result is acceptable for the test
+    assert!(result.is_ok()); // An empty list is acceptable for the test
+
+    let result = d.port_ls("http".to_string());
+    assert!(matches!(result, Err(Error::InvalidPort(_))));
+}
+
+#[test]
+fn test_process() {
+    let mut d = LsofData::new();
+    let me = d.process_ls(std::process::id().to_string()).unwrap();
+    assert!(!me.fds.is_empty());
+
+    let result = d.process_ls(u32::MAX.to_string());
+    assert!(matches!(result, Err(Error::ProcessVanished { .. })));
 }
 
+#[test]
+fn test_get_file_info() {
+    let d = LsofData::new();
+    let status = GetFileInfo::get_pid_info(&d, "/proc/self/status".to_string()).unwrap();
+    assert!(status.contains_key("Name"));
+    assert!(!GetFileInfo::get_mem_info(&d, "/proc/self/maps".to_string()).unwrap().is_empty());
+    assert!(matches!(GetFileInfo::get_pid_info(&d, "/nonexistent/status".to_string()), Err(Error::Io(_))));
+    assert!(matches!(GetFileInfo::get_port_info(&d, "http"), Err(Error::InvalidPort(_))));
+}
 
+#[test]
+fn test_scan_self_skips_handle() {
+    let me = std::process::id().to_string();
+    let process = ProcessHandle::open(&me).unwrap();
+    let own = process.own_fds();
+    assert!(!own.is_empty());
+
+    let d = LsofData::new();
+    let info = d.scan_process(&me, &process, &mut Diagnostics::default()).unwrap();
+    assert!(info.fds.iter().all(|entry| !own.contains(&entry.fd)));
+    // Handles on other processes hold nothing open in them
+    let init = ProcessHandle::open("1").unwrap();
+    assert!(me == "1" || init.own_fds().is_empty());
+}
+
+#[test]
+fn test_rescan_drops_closed_files() {
+    let path = std::env::temp_dir().join(format!("minilsof-rescan-{}", std::process::id()));
+    let file = fs::File::create(&path).unwrap();
+    let me = std::process::id().to_string();
+
+    let mut d = LsofData::new();
+    let held = d.target_file_ls(path.to_string_lossy().to_string()).unwrap();
+    assert!(held.iter().any(|info| info.pid == me));
+    assert!(d.scanned_at().is_some());
+
+    drop(file);
+    let held = d.target_file_ls(path.to_string_lossy().to_string()).unwrap();
+    assert!(held.iter().all(|info| info.pid != me));
+
+    // A port query after a full listing must not return the listing's processes
+    d.file_ls().unwrap();
+    let by_port = d.port_ls("1".to_string()).unwrap();
+    assert!(by_port.iter().all(|info| info.pid != me));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_scan_diagnostics() {
+    let mut d = LsofData::new();
+    let scan = d.scan().unwrap();
+    assert!(scan.processes.contains_key(&std::process::id().to_string()));
+    assert_eq!(&scan.diagnostics, d.diagnostics());
+
+    let mut diagnostics = Diagnostics::default();
+    diagnostics.skipped("1", Error::PermissionDenied { pid: "1".to_string() });
+    diagnostics.skipped("2", Error::ProcessVanished { pid: "2".to_string() });
+    assert_eq!(diagnostics.permission_denied, ["1"]);
+    assert_eq!(diagnostics.vanished, ["2"]);
+    assert!(!diagnostics.is_complete());
+}
@@ -0,0 +1,144 @@
+use crate::{LsofData, LsofFiletype, Result};
+use glob::Pattern;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A process mapping a library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryUser {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// Bytes of the library mapped by the process
+    pub mapped: u64,
+    /// Whether any of the mappings is executable
+    pub executable: bool,
+}
+
+/// A mapped library and the processes mapping it
+///
+/// Libraries are told apart by inode as well as path, so an upgraded
+/// library and the old version still mapped by running processes are
+/// reported separately.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Library {
+    /// Path of the library, without any ` (deleted)` suffix
+    pub path: PathBuf,
+    /// Device of the mapped file, as `(major, minor)`
+    pub device: (u32, u32),
+    /// Inode of the mapped file
+    pub inode: u64,
+    /// Version taken from the file name, such as `3` for `libssl.so.3`
+    pub version: Option<String>,
+    /// Whether the mapped file was unlinked
+    pub deleted: bool,
+    /// Processes mapping the library, sorted by pid
+    pub processes: Vec<LibraryUser>,
+}
+
+/// Take the version from the file name of a shared object
+///
+/// Handles both `libssl.so.3` and `libc-2.31.so` style names.
+pub fn library_version(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    if let Some((_, version)) = name.split_once(".so.") {
+        return Some(version.to_string());
+    }
+    let (_, version) = name.strip_suffix(".so")?.rsplit_once('-')?;
+    version.starts_with(|c: char| c.is_ascii_digit()).then(|| version.to_string())
+}
+
+impl LsofData {
+    /// Get the mapped files matching a glob pattern, such as `libssl.so*`,
+    /// and the processes mapping them
+    ///
+    /// The pattern is matched against both the full path and the file name.
+    /// Only `/proc/<pid>/maps` is read; open descriptors are not looked at.
+    pub fn library_ls(&mut self, pattern: &str) -> Result<Vec<Library>> {
+        let pattern = Pattern::new(pattern)?;
+        self.target_filetype = Some(LsofFiletype::Mem);
+
+        let mappings = self.visit_processes(|data, pid, process, _| {
+            let entries = data.get_maps(process).map_err(|err| Self::pid_error(pid, err))?;
+            let name = data.get_pid_info(process, pid)?.remove("Name");
+            Ok((pid.to_string(), name, entries))
+        })?;
+
+        let mut libraries: BTreeMap<(PathBuf, u64, (u32, u32)), Library> = BTreeMap::new();
+        for (pid, name, entries) in mappings {
+            for entry in entries {
+                let Some(path) = entry.file_path() else {
+                    continue;
+                };
+                let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                if !pattern.matches_path(path) && !pattern.matches(file_name) {
+                    continue;
+                }
+
+                let key = (path.to_path_buf(), entry.inode, entry.device);
+                let library = libraries.entry(key).or_insert_with(|| Library {
+                    path: path.to_path_buf(),
+                    device: entry.device,
+                    inode: entry.inode,
+                    version: library_version(path),
+                    deleted: entry.is_deleted(),
+                    processes: Vec::new(),
+                });
+                // Maps are grouped per process, so its mappings come in one run
+                match library.processes.last_mut() {
+                    Some(user) if user.pid == pid => {
+                        user.mapped += entry.size();
+                        user.executable |= entry.perms.execute;
+                    }
+                    _ => library.processes.push(LibraryUser {
+                        pid: pid.clone(),
+                        name: name.clone(),
+                        mapped: entry.size(),
+                        executable: entry.perms.execute,
+                    }),
+                }
+            }
+        }
+
+        let mut libraries: Vec<Library> = libraries.into_values().collect();
+        for library in &mut libraries {
+            library.processes.sort_by_key(|user| user.pid.parse::<u64>().unwrap_or(u64::MAX));
+        }
+        Ok(libraries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_version() {
+        assert_eq!(library_version(Path::new("/usr/lib/libssl.so.3")), Some("3".to_string()));
+        assert_eq!(library_version(Path::new("libstdc++.so.6.0.30")), Some("6.0.30".to_string()));
+        assert_eq!(library_version(Path::new("/lib/libc-2.31.so")), Some("2.31".to_string()));
+        assert_eq!(library_version(Path::new("/lib/libfoo.so")), None);
+        assert_eq!(library_version(Path::new("/lib/libnss-files.so")), None);
+    }
+
+    #[test]
+    fn test_library_ls() {
+        // The test binary itself is mapped executable by this process
+        let exe = std::env::current_exe().unwrap();
+        let name = exe.file_name().unwrap().to_str().unwrap().to_string();
+
+        let mut d = LsofData::new();
+        let libraries = d.library_ls(&name).unwrap();
+        let me = std::process::id().to_string();
+        let user = libraries
+            .iter()
+            .flat_map(|library| &library.processes)
+            .find(|user| user.pid == me)
+            .expect("test binary was not found in its own maps");
+        assert!(user.executable);
+        assert!(user.mapped > 0);
+
+        assert!(matches!(d.library_ls("[unclosed"), Err(crate::Error::Glob(_))));
+    }
+}
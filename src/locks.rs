@@ -0,0 +1,324 @@
+use crate::mountinfo;
+use crate::{Error, Fdinfo, LsofData, Result};
+use std::collections::HashMap;
+use std::fs::{self, read_to_string};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Kind of file lock
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LockKind {
+    /// Process-associated record lock from `fcntl(F_SETLK)` or `lockf`
+    Posix,
+    /// Whole-file lock from `flock`
+    Flock,
+    /// Open file description lock from `fcntl(F_OFD_SETLK)`
+    Ofd,
+    /// File lease from `fcntl(F_SETLEASE)`
+    Lease,
+    /// A kind this parser does not know, such as `DELEG`
+    Other(String),
+}
+
+/// Access a lock grants or asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockAccess {
+    /// Shared lock
+    Read,
+    /// Exclusive lock
+    Write,
+    /// A lease being broken down to no access
+    Unlock,
+}
+
+/// A single lock from `/proc/locks`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLock {
+    /// Ordinal of the lock; waiters share it with the lock blocking them
+    pub id: u32,
+    /// Kind of lock
+    pub kind: LockKind,
+    /// `ADVISORY` or `MANDATORY` for locks, the lease state for leases
+    pub mode: String,
+    /// Access granted, or asked for by a waiter
+    pub access: LockAccess,
+    /// Process that took the lock, `None` for OFD locks not tied to one
+    pub pid: Option<u32>,
+    /// Device of the locked file, as `(major, minor)`
+    pub device: (u32, u32),
+    /// Inode of the locked file
+    pub inode: u64,
+    /// First byte of the locked range
+    pub start: u64,
+    /// Last byte of the locked range, `None` for end of file
+    pub end: Option<u64>,
+    /// Whether this is a request waiting for the lock rather than a lock held
+    pub waiting: bool,
+}
+
+/// Parse one line of `/proc/locks`
+fn parse_line(line: &str) -> Option<FileLock> {
+    let (id, rest) = line.split_once(':')?;
+    let mut fields: Vec<&str> = rest.split_whitespace().collect();
+    // Waiters are marked with "->" and listed after the lock blocking them
+    let waiting = fields.first() == Some(&"->");
+    if waiting {
+        fields.remove(0);
+    }
+    if fields.len() < 7 {
+        return None;
+    }
+
+    let kind = match fields[0] {
+        "POSIX" => LockKind::Posix,
+        "FLOCK" => LockKind::Flock,
+        "OFDLCK" => LockKind::Ofd,
+        "LEASE" => LockKind::Lease,
+        other => LockKind::Other(other.to_string()),
+    };
+    let access = match fields[2] {
+        "READ" => LockAccess::Read,
+        "WRITE" => LockAccess::Write,
+        "UNLCK" => LockAccess::Unlock,
+        _ => return None,
+    };
+    // Major and minor are hex, the inode decimal
+    let mut file = fields[4].splitn(3, ':');
+    let major = u32::from_str_radix(file.next()?, 16).ok()?;
+    let minor = u32::from_str_radix(file.next()?, 16).ok()?;
+    let inode = file.next()?.parse().ok()?;
+
+    Some(FileLock {
+        id: id.trim().parse().ok()?,
+        kind,
+        mode: fields[1].to_string(),
+        access,
+        pid: fields[3].parse().ok(),
+        device: (major, minor),
+        inode,
+        start: fields[5].parse().ok()?,
+        end: match fields[6] {
+            "EOF" => None,
+            end => Some(end.parse().ok()?),
+        },
+        waiting,
+    })
+}
+
+/// Parse the contents of `/proc/locks`, skipping malformed lines
+pub fn parse(content: &str) -> Vec<FileLock> {
+    content.lines().filter_map(parse_line).collect()
+}
+
+/// Read every lock on the system from `/proc/locks`
+pub fn read() -> io::Result<Vec<FileLock>> {
+    Ok(parse(&read_to_string("/proc/locks")?))
+}
+
+/// A lock together with the processes behind it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockHolder {
+    /// The lock
+    pub lock: FileLock,
+    /// The process that took the lock; for flock, OFD locks and leases,
+    /// which belong to an open file rather than a process, every process
+    /// with the file open unless the one that took it still has it open
+    pub processes: Vec<Fdinfo>,
+}
+
+/// Locks on a file, held and waited for
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct LockReport {
+    /// Locks held on the file
+    pub holders: Vec<LockHolder>,
+    /// Lock requests blocked on the file
+    pub waiters: Vec<LockHolder>,
+}
+
+/// Get the processes behind a lock, given every process and those with the
+/// locked file open
+///
+/// Only POSIX locks go away with the process that took them; the others
+/// belong to the open file, whose taker may have exited, had its pid reused,
+/// or handed the descriptor on, so their pid is trusted only while that
+/// process still has the file open.
+fn lock_processes(lock: &FileLock, processes: &HashMap<String, Fdinfo>, openers: &[Fdinfo]) -> Vec<Fdinfo> {
+    let pid = lock.pid.map(|pid| pid.to_string());
+    if lock.kind == LockKind::Posix {
+        return pid.and_then(|pid| processes.get(&pid)).cloned().into_iter().collect();
+    }
+    match openers.iter().find(|info| Some(&info.pid) == pid.as_ref()) {
+        Some(info) => vec![info.clone()],
+        None => openers.to_vec(),
+    }
+}
+
+impl LsofData {
+    /// Get the locks held and waited for on a file
+    ///
+    /// The file is matched by device and inode, both in `/proc/locks` and
+    /// against the descriptors of every process, so locks and descriptors
+    /// taken through another path to the same file are found too.
+    pub fn lock_ls(&mut self, path: impl AsRef<Path>) -> Result<LockReport> {
+        let path = path.as_ref();
+        let meta = fs::metadata(path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => Error::TargetNotFound(path.to_path_buf()),
+            _ => Error::Io(err),
+        })?;
+        let device = mountinfo::dev_major_minor(meta.dev());
+
+        let locks: Vec<FileLock> = read()
+            .map_err(|_| Error::ProcfsUnavailable)?
+            .into_iter()
+            .filter(|lock| lock.device == device && lock.inode == meta.ino())
+            .collect();
+        let mut report = LockReport::default();
+        if locks.is_empty() {
+            self.reset();
+            return Ok(report);
+        }
+
+        // Processes with the file open, for locks that belong to an open file
+        self.target_filetype = None;
+        self.target_filename = String::new();
+        let scanned = self.visit_processes(|data, pid, process, diagnostics| {
+            let info = data.scan_process(pid, process, diagnostics)?;
+            let mut opens = false;
+            for entry in info.fds.iter().filter(|entry| entry.link.starts_with('/')) {
+                let fd_name = format!("fd/{}", entry.fd);
+                match process.stat(&fd_name) {
+                    Ok(stat) => opens |= mountinfo::dev_major_minor(stat.dev) == device && stat.ino == meta.ino(),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) if Self::is_gone(&err) => return Err(Error::ProcessVanished { pid: pid.to_string() }),
+                    Err(err) => diagnostics.unreadable(format!("/proc/{}/{}", pid, fd_name), &err),
+                }
+            }
+            Ok((info, opens))
+        })?;
+        let mut openers = Vec::new();
+        for (info, opens) in scanned {
+            if opens {
+                openers.push(info.clone());
+            }
+            self.pidmap.insert(info.pid.clone(), info);
+        }
+
+        for lock in locks {
+            let processes = lock_processes(&lock, &self.pidmap, &openers);
+            let holder = LockHolder { lock, processes };
+            if holder.lock.waiting {
+                report.waiters.push(holder);
+            } else {
+                report.holders.push(holder);
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn test_parse_locks() {
+        let content = "\
+1: POSIX  ADVISORY  WRITE 1234 08:01:131090 0 EOF
+1: -> POSIX  ADVISORY  WRITE 1240 08:01:131090 0 99
+2: FLOCK  ADVISORY  READ 5678 00:1a:999 0 EOF
+3: OFDLCK ADVISORY  WRITE -1 fd:02:42 100 199
+4: LEASE  BREAKING  UNLCK 77 08:01:55 0 EOF";
+        let locks = parse(content);
+        assert_eq!(locks.len(), 5);
+
+        assert_eq!(locks[0].kind, LockKind::Posix);
+        assert_eq!(locks[0].pid, Some(1234));
+        assert_eq!(locks[0].device, (8, 1));
+        assert_eq!(locks[0].end, None);
+        assert!(!locks[0].waiting);
+
+        assert!(locks[1].waiting);
+        assert_eq!(locks[1].id, 1);
+        assert_eq!(locks[1].end, Some(99));
+
+        assert_eq!(locks[2].access, LockAccess::Read);
+        assert_eq!(locks[2].device, (0, 0x1a));
+
+        assert_eq!(locks[3].kind, LockKind::Ofd);
+        assert_eq!(locks[3].pid, None);
+        assert_eq!((locks[3].device, locks[3].inode), ((0xfd, 2), 42));
+
+        assert_eq!(locks[4].kind, LockKind::Lease);
+        assert_eq!(locks[4].mode, "BREAKING");
+        assert_eq!(locks[4].access, LockAccess::Unlock);
+    }
+
+    #[test]
+    fn test_lock_ls() {
+        let path = std::env::temp_dir().join(format!("minilsof-lock-{}", std::process::id()));
+        let file = fs::File::create(&path).unwrap();
+        // SAFETY: flock only takes an open fd and an operation
+        assert_eq!(unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) }, 0);
+
+        let mut d = LsofData::new();
+        let report = d.lock_ls(&path).unwrap();
+        let me = std::process::id().to_string();
+        let holder = report.holders.iter().find(|h| h.lock.kind == LockKind::Flock).expect("flock not found");
+        assert_eq!(holder.lock.access, LockAccess::Write);
+        assert!(holder.processes.iter().any(|p| p.pid == me));
+        assert!(report.waiters.is_empty());
+
+        drop(file);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(d.lock_ls(&path), Err(Error::TargetNotFound(_))));
+    }
+
+    #[test]
+    fn test_lock_processes() {
+        let info = |pid: &str| Fdinfo { pid: pid.to_string(), ..Default::default() };
+        let processes: HashMap<String, Fdinfo> = ["1", "20", "30"].map(|pid| (pid.to_string(), info(pid))).into();
+        let openers = [info("20"), info("30")];
+        let lock = |kind, pid| parse_line(&format!("1: {} ADVISORY WRITE {} 08:01:42 0 EOF", kind, pid)).unwrap();
+        let pids = |found: Vec<Fdinfo>| found.into_iter().map(|info| info.pid).collect::<Vec<_>>();
+
+        assert_eq!(pids(lock_processes(&lock("POSIX", 1), &processes, &openers)), ["1"]);
+        assert_eq!(pids(lock_processes(&lock("POSIX", 99), &processes, &openers)), Vec::<String>::new());
+        assert_eq!(pids(lock_processes(&lock("FLOCK", 30), &processes, &openers)), ["30"]);
+        // A reused pid that does not have the file open is not the holder
+        assert_eq!(pids(lock_processes(&lock("FLOCK", 1), &processes, &openers)), ["20", "30"]);
+        assert_eq!(pids(lock_processes(&lock("OFDLCK", -1), &processes, &openers)), ["20", "30"]);
+        assert_eq!(pids(lock_processes(&lock("LEASE", 99), &processes, &openers)), ["20", "30"]);
+    }
+
+    #[test]
+    fn test_lock_ls_taker_exited() {
+        let path = std::env::temp_dir().join(format!("minilsof-lock-exited-{}", std::process::id()));
+        let link = std::env::temp_dir().join(format!("minilsof-lock-link-{}", std::process::id()));
+        let file = fs::File::create(&path).unwrap();
+        fs::hard_link(&path, &link).unwrap();
+
+        // The lock is taken by a child that exits, leaving it on our open file
+        // SAFETY: the child only calls flock and _exit, both async-signal-safe
+        let child = unsafe { libc::fork() };
+        if child == 0 {
+            // SAFETY: as above
+            unsafe { libc::_exit((libc::flock(file.as_raw_fd(), libc::LOCK_EX) != 0) as i32) };
+        }
+        let mut status = 0;
+        // SAFETY: `child` is our child and `status` outlives the call
+        assert_eq!(unsafe { libc::waitpid(child, &mut status, 0) }, child);
+        assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+
+        let mut d = LsofData::new();
+        let report = d.lock_ls(&link).unwrap();
+        let me = std::process::id().to_string();
+        let holder = report.holders.iter().find(|h| h.lock.kind == LockKind::Flock).expect("flock not found");
+        assert!(holder.processes.iter().any(|p| p.pid == me));
+
+        drop(file);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&link).unwrap();
+    }
+}
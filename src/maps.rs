@@ -0,0 +1,227 @@
+use std::ops::AddAssign;
+use std::path::Path;
+
+/// Suffix the kernel appends to the path of an unlinked file
+pub(crate) const DELETED_SUFFIX: &str = " (deleted)";
+
+/// Access permissions of a mapping
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Perms {
+    /// Readable
+    pub read: bool,
+    /// Writable
+    pub write: bool,
+    /// Executable
+    pub execute: bool,
+    /// Shared rather than private (copy-on-write)
+    pub shared: bool,
+}
+
+impl Perms {
+    /// Parse a permission field such as `r-xp`
+    fn parse(field: &str) -> Option<Self> {
+        let bytes = field.as_bytes();
+        if bytes.len() != 4 {
+            return None;
+        }
+        Some(Self {
+            read: bytes[0] == b'r',
+            write: bytes[1] == b'w',
+            execute: bytes[2] == b'x',
+            shared: bytes[3] == b's',
+        })
+    }
+}
+
+/// A single line of `/proc/<pid>/maps`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MapEntry {
+    /// Start address
+    pub start: u64,
+    /// End address (exclusive)
+    pub end: u64,
+    /// Access permissions
+    pub perms: Perms,
+    /// Offset into the mapped file
+    pub offset: u64,
+    /// Device of the mapped file, as `(major, minor)`
+    pub device: (u32, u32),
+    /// Inode of the mapped file, 0 for anonymous mappings
+    pub inode: u64,
+    /// Path name, pseudo-name such as `[heap]`, or `None` when anonymous
+    pub path: Option<String>,
+}
+
+impl MapEntry {
+    /// Size of the mapping in bytes
+    pub fn size(&self) -> u64 {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Whether the mapped file was unlinked
+    pub fn is_deleted(&self) -> bool {
+        self.path.as_deref().is_some_and(|path| path.ends_with(DELETED_SUFFIX))
+    }
+
+    /// Path of the mapped file, without any ` (deleted)` suffix
+    ///
+    /// `None` for anonymous mappings and pseudo-names such as `[stack]`.
+    pub fn file_path(&self) -> Option<&Path> {
+        let path = self.path.as_deref()?;
+        if !path.starts_with('/') {
+            return None;
+        }
+        Some(Path::new(path.strip_suffix(DELETED_SUFFIX).unwrap_or(path)))
+    }
+}
+
+/// Split off the next space-separated field
+fn next_field(rest: &str) -> Option<(&str, &str)> {
+    let rest = rest.trim_start_matches(' ');
+    if rest.is_empty() {
+        return None;
+    }
+    Some(rest.split_once(' ').unwrap_or((rest, "")))
+}
+
+/// Parse one maps line; the path may itself contain spaces
+fn parse_line(line: &str) -> Option<MapEntry> {
+    let (range, rest) = next_field(line)?;
+    let (perms, rest) = next_field(rest)?;
+    let (offset, rest) = next_field(rest)?;
+    let (device, rest) = next_field(rest)?;
+    let (inode, rest) = next_field(rest)?;
+
+    let (start, end) = range.split_once('-')?;
+    let (major, minor) = device.split_once(':')?;
+    let path = rest.trim_start_matches(' ');
+
+    Some(MapEntry {
+        start: u64::from_str_radix(start, 16).ok()?,
+        end: u64::from_str_radix(end, 16).ok()?,
+        perms: Perms::parse(perms)?,
+        offset: u64::from_str_radix(offset, 16).ok()?,
+        device: (u32::from_str_radix(major, 16).ok()?, u32::from_str_radix(minor, 16).ok()?),
+        inode: inode.parse().ok()?,
+        path: (!path.is_empty()).then(|| path.to_string()),
+    })
+}
+
+/// Parse the contents of a maps file, skipping malformed lines
+pub fn parse(content: &str) -> Vec<MapEntry> {
+    content.lines().filter_map(parse_line).collect()
+}
+
+/// Memory use of a mapping, from `/proc/<pid>/smaps`, in bytes
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Resident in memory
+    pub rss: u64,
+    /// Proportional share of the resident memory
+    pub pss: u64,
+    /// Resident and also mapped by other processes
+    pub shared: u64,
+    /// Resident and mapped by this process only
+    pub private: u64,
+    /// Swapped out
+    pub swap: u64,
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.rss += other.rss;
+        self.pss += other.pss;
+        self.shared += other.shared;
+        self.private += other.private;
+        self.swap += other.swap;
+    }
+}
+
+impl MemoryUsage {
+    /// Account one `Key:   123 kB` line of smaps
+    fn add_line(&mut self, key: &str, value: &str) {
+        let Some(kb) = value.split_whitespace().next().and_then(|v| v.parse::<u64>().ok()) else {
+            return;
+        };
+        let bytes = kb * 1024;
+        match key {
+            "Rss" => self.rss += bytes,
+            "Pss" => self.pss += bytes,
+            "Shared_Clean" | "Shared_Dirty" => self.shared += bytes,
+            "Private_Clean" | "Private_Dirty" => self.private += bytes,
+            "Swap" => self.swap += bytes,
+            _ => {}
+        }
+    }
+}
+
+/// Parse the contents of an smaps file into mappings and their memory use
+pub fn parse_smaps(content: &str) -> Vec<(MapEntry, MemoryUsage)> {
+    let mut mappings: Vec<(MapEntry, MemoryUsage)> = Vec::new();
+    for line in content.lines() {
+        if let Some(entry) = parse_line(line) {
+            mappings.push((entry, MemoryUsage::default()));
+        } else if let (Some((key, value)), Some((_, usage))) = (line.split_once(':'), mappings.last_mut()) {
+            usage.add_line(key, value);
+        }
+    }
+    mappings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_maps() {
+        let content = "\
+55d0c0a00000-55d0c0a28000 r--p 00000000 08:01 1311 /usr/bin/my app
+7f12a0000000-7f12a0021000 rw-p 00000000 00:00 0
+7f12a4000000-7f12a4195000 r-xp 00028000 fd:01 2097 /usr/lib/libssl.so.3 (deleted)
+7ffd4c000000-7ffd4c021000 rw-p 00000000 00:00 0                          [stack]";
+        let entries = parse(content);
+        assert_eq!(entries.len(), 4);
+
+        assert_eq!(entries[0].path.as_deref(), Some("/usr/bin/my app"));
+        assert_eq!(entries[0].device, (8, 1));
+        assert_eq!(entries[0].size(), 0x28000);
+
+        assert_eq!(entries[1].path, None);
+        assert!(entries[1].perms.write && !entries[1].perms.execute);
+
+        assert!(entries[2].is_deleted());
+        assert!(entries[2].perms.execute);
+        assert_eq!(entries[2].device, (0xfd, 1));
+        assert_eq!(entries[2].offset, 0x28000);
+        assert_eq!(entries[2].file_path(), Some(Path::new("/usr/lib/libssl.so.3")));
+
+        assert_eq!(entries[3].path.as_deref(), Some("[stack]"));
+        assert_eq!(entries[3].file_path(), None);
+    }
+
+    #[test]
+    fn test_parse_smaps() {
+        let content = "\
+7f12a4000000-7f12a4195000 r-xp 00028000 fd:01 2097 /usr/lib/libssl.so.3
+Size:               1620 kB
+Rss:                 800 kB
+Pss:                 200 kB
+Shared_Clean:        780 kB
+Shared_Dirty:          0 kB
+Private_Clean:        12 kB
+Private_Dirty:         8 kB
+Swap:                  4 kB
+THPeligible:    0
+VmFlags: rd ex mr mw me sd
+7ffd4c000000-7ffd4c021000 rw-p 00000000 00:00 0                          [stack]
+Rss:                  16 kB";
+        let mappings = parse_smaps(content);
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0].0.inode, 2097);
+        assert_eq!(
+            mappings[0].1,
+            MemoryUsage { rss: 800 * 1024, pss: 200 * 1024, shared: 780 * 1024, private: 20 * 1024, swap: 4 * 1024 }
+        );
+        assert_eq!(mappings[1].1.rss, 16 * 1024);
+    }
+}
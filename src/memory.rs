@@ -0,0 +1,141 @@
+use crate::maps::{self, MemoryUsage};
+use crate::{LsofData, LsofFiletype, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Memory used by one mapped file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMemory {
+    /// Path of the file, without any ` (deleted)` suffix
+    pub path: PathBuf,
+    /// Device of the file, as `(major, minor)`
+    pub device: (u32, u32),
+    /// Inode of the file
+    pub inode: u64,
+    /// Memory use summed over the file's mappings
+    pub usage: MemoryUsage,
+}
+
+/// Memory used by a process, split by mapped file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessMemory {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// Memory use of all mappings, anonymous ones included
+    pub total: MemoryUsage,
+    /// Memory use per mapped file, largest Rss first
+    pub files: Vec<FileMemory>,
+}
+
+/// Memory used by a mapped file across every process mapping it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMemoryTotal {
+    /// The file and its memory use summed over all processes
+    ///
+    /// Pages shared between processes count once per process in `rss`;
+    /// `pss` divides them between the processes and adds up to what the
+    /// file really costs.
+    pub file: FileMemory,
+    /// Processes mapping the file, sorted by pid
+    pub pids: Vec<String>,
+}
+
+/// Memory use per process and per mapped file
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// Processes, sorted by pid
+    pub processes: Vec<ProcessMemory>,
+    /// Mapped files across all processes, largest Pss first
+    pub files: Vec<FileMemoryTotal>,
+}
+
+impl MemoryReport {
+    /// Sum the per-process figures per file
+    fn new(mut processes: Vec<ProcessMemory>) -> Self {
+        processes.sort_by_key(|process| process.pid.parse::<u64>().unwrap_or(u64::MAX));
+
+        let mut totals: HashMap<(&PathBuf, (u32, u32), u64), FileMemoryTotal> = HashMap::new();
+        for process in &processes {
+            for file in &process.files {
+                let total = totals.entry((&file.path, file.device, file.inode)).or_insert_with(|| FileMemoryTotal {
+                    file: FileMemory { usage: MemoryUsage::default(), ..file.clone() },
+                    pids: Vec::new(),
+                });
+                total.file.usage += file.usage;
+                total.pids.push(process.pid.clone());
+            }
+        }
+        let mut files: Vec<FileMemoryTotal> = totals.into_values().collect();
+        files.sort_by(|a, b| b.file.usage.pss.cmp(&a.file.usage.pss).then(a.file.path.cmp(&b.file.path)));
+
+        Self { processes, files }
+    }
+}
+
+impl LsofData {
+    /// Get the resident, proportional, shared, private and swapped memory
+    /// of every process, per mapped file, from `/proc/<pid>/smaps`
+    ///
+    /// The report also sums the figures per file across processes, to show
+    /// which files contribute most to memory use. Processes without any
+    /// mappings, such as kernel threads, are left out.
+    pub fn memory_ls(&mut self) -> Result<MemoryReport> {
+        self.target_filetype = Some(LsofFiletype::Mem);
+
+        let processes = self.visit_processes(|data, pid, process, _| {
+            let content = process.read_to_string("smaps").map_err(|err| Self::pid_error(pid, err))?;
+            let mappings = maps::parse_smaps(&content);
+            if mappings.is_empty() {
+                return Ok(None);
+            }
+
+            let mut total = MemoryUsage::default();
+            let mut files: HashMap<(PathBuf, (u32, u32), u64), MemoryUsage> = HashMap::new();
+            for (entry, usage) in &mappings {
+                total += *usage;
+                if let Some(path) = entry.file_path() {
+                    *files.entry((path.to_path_buf(), entry.device, entry.inode)).or_default() += *usage;
+                }
+            }
+            let mut files: Vec<FileMemory> = files
+                .into_iter()
+                .map(|((path, device, inode), usage)| FileMemory { path, device, inode, usage })
+                .collect();
+            files.sort_by(|a, b| b.usage.rss.cmp(&a.usage.rss).then(a.path.cmp(&b.path)));
+
+            Ok(Some(ProcessMemory {
+                pid: pid.to_string(),
+                name: data.get_pid_info(process, pid)?.remove("Name"),
+                total,
+                files,
+            }))
+        })?;
+        Ok(MemoryReport::new(processes.into_iter().flatten().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_ls() {
+        let mut d = LsofData::new();
+        let report = d.memory_ls().unwrap();
+        let me = std::process::id().to_string();
+        let process = report.processes.iter().find(|p| p.pid == me).expect("own process missing");
+        assert!(process.total.rss > 0);
+
+        // The test binary is mapped, and part of it must be resident
+        let exe = std::env::current_exe().unwrap();
+        let file = process.files.iter().find(|f| f.path == exe).expect("test binary missing");
+        assert!(file.usage.rss > 0);
+        assert!(process.total.rss >= file.usage.rss);
+
+        let total = report.files.iter().find(|t| t.file.path == exe).unwrap();
+        assert!(total.pids.contains(&me));
+        assert!(total.file.usage.rss >= file.usage.rss);
+    }
+}
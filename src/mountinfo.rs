@@ -0,0 +1,145 @@
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+/// A single line of `/proc/<pid>/mountinfo`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    /// Unique ID of the mount
+    pub mount_id: u32,
+    /// ID of the parent mount
+    pub parent_id: u32,
+    /// Major number of the device holding the filesystem
+    pub major: u32,
+    /// Minor number of the device holding the filesystem
+    pub minor: u32,
+    /// Directory of the filesystem that forms the root of this mount
+    pub root: String,
+    /// Mount point relative to the process's root directory
+    pub mount_point: PathBuf,
+    /// Per-mount options
+    pub mount_options: String,
+    /// Filesystem type, such as `ext4` or `proc`
+    pub fs_type: String,
+    /// Filesystem-specific source, such as `/dev/sda1`
+    pub source: String,
+    /// Per-superblock options
+    pub super_options: String,
+}
+
+impl MountInfo {
+    /// Value of `option` on the mount or its superblock, empty for plain flags
+    pub fn option(&self, option: &str) -> Option<&str> {
+        self.mount_options
+            .split(',')
+            .chain(self.super_options.split(','))
+            .find_map(|opt| match opt.split_once('=') {
+                Some((key, value)) if key == option => Some(value),
+                None if opt == option => Some(""),
+                _ => None,
+            })
+    }
+}
+
+/// Split a userspace `dev_t`, as found in `st_dev`, into major and minor numbers
+pub fn dev_major_minor(dev: u64) -> (u32, u32) {
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0000_0fff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0x0000_00ff);
+    (major as u32, minor as u32)
+}
+
+/// Find the mount of the filesystem on device `major:minor`
+///
+/// Bind mounts share a device, so a mount of the filesystem root is
+/// preferred over one of a subdirectory.
+pub fn find_device(mounts: &[MountInfo], major: u32, minor: u32) -> Option<&MountInfo> {
+    let mut matching = mounts.iter().filter(|m| m.major == major && m.minor == minor);
+    let first = matching.next()?;
+    Some(std::iter::once(first).chain(matching).find(|m| m.root == "/").unwrap_or(first))
+}
+
+/// Undo the octal escapes (`\040` for a space) used in mountinfo fields
+pub(crate) fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(digits, 8) {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parse the contents of a mountinfo file, skipping malformed lines
+pub fn parse(content: &str) -> Vec<MountInfo> {
+    content.lines().filter_map(parse_line).collect()
+}
+
+/// Parse one mountinfo line
+fn parse_line(line: &str) -> Option<MountInfo> {
+    // Optional fields end with a lone "-" separator
+    let (left, right) = line.split_once(" - ")?;
+    let fields: Vec<&str> = left.split_whitespace().collect();
+    let tail: Vec<&str> = right.split_whitespace().collect();
+    if fields.len() < 6 || tail.len() < 2 {
+        return None;
+    }
+    let (major, minor) = fields[2].split_once(':')?;
+
+    Some(MountInfo {
+        mount_id: fields[0].parse().ok()?,
+        parent_id: fields[1].parse().ok()?,
+        major: major.parse().ok()?,
+        minor: minor.parse().ok()?,
+        root: unescape(fields[3]),
+        mount_point: PathBuf::from(unescape(fields[4])),
+        mount_options: fields[5].to_string(),
+        fs_type: tail[0].to_string(),
+        source: unescape(tail[1]),
+        super_options: tail.get(2).unwrap_or(&"").to_string(),
+    })
+}
+
+/// Read the mount table as seen by process `pid` (or `"self"`)
+pub fn read(pid: &str) -> io::Result<Vec<MountInfo>> {
+    Ok(parse(&read_to_string(format!("/proc/{}/mountinfo", pid))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let content = "\
+22 1 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw,hidepid=invisible,subset=pid
+36 22 8:1 /data /mnt/my\\040disk rw,relatime - ext4 /dev/sda1 rw
+garbage line";
+        let mounts = parse(content);
+        assert_eq!(mounts.len(), 2);
+
+        assert_eq!(mounts[0].fs_type, "proc");
+        assert_eq!(mounts[0].option("hidepid"), Some("invisible"));
+        assert_eq!(mounts[0].option("subset"), Some("pid"));
+        assert_eq!(mounts[0].option("nosuid"), Some(""));
+        assert_eq!(mounts[0].option("gid"), None);
+
+        assert_eq!((mounts[1].major, mounts[1].minor), (8, 1));
+        assert_eq!(mounts[1].mount_point, PathBuf::from("/mnt/my disk"));
+        assert_eq!(mounts[1].root, "/data");
+
+        assert_eq!(find_device(&mounts, 8, 1).map(|m| m.mount_id), Some(36));
+        assert_eq!(find_device(&mounts, 8, 2), None);
+        assert_eq!(dev_major_minor(0x801), (8, 1));
+        assert_eq!(dev_major_minor(0x1_0301), (259, 1));
+        assert_eq!(dev_major_minor(0x10_0800), (8, 256));
+    }
+}
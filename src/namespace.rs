@@ -0,0 +1,277 @@
+use crate::mountinfo;
+use crate::{FdEntry, LsofData, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Kind of namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NamespaceKind {
+    /// cgroup namespace
+    Cgroup,
+    /// IPC namespace
+    Ipc,
+    /// Mount namespace
+    Mnt,
+    /// Network namespace
+    Net,
+    /// PID namespace
+    Pid,
+    /// Time namespace
+    Time,
+    /// User namespace
+    User,
+    /// UTS (hostname) namespace
+    Uts,
+}
+
+impl NamespaceKind {
+    /// Name as used in `/proc/<pid>/ns` links
+    pub fn name(&self) -> &'static str {
+        match self {
+            NamespaceKind::Cgroup => "cgroup",
+            NamespaceKind::Ipc => "ipc",
+            NamespaceKind::Mnt => "mnt",
+            NamespaceKind::Net => "net",
+            NamespaceKind::Pid => "pid",
+            NamespaceKind::Time => "time",
+            NamespaceKind::User => "user",
+            NamespaceKind::Uts => "uts",
+        }
+    }
+
+    /// Convert a name from a link
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "cgroup" => NamespaceKind::Cgroup,
+            "ipc" => NamespaceKind::Ipc,
+            "mnt" => NamespaceKind::Mnt,
+            "net" => NamespaceKind::Net,
+            "pid" => NamespaceKind::Pid,
+            "time" => NamespaceKind::Time,
+            "user" => NamespaceKind::User,
+            "uts" => NamespaceKind::Uts,
+            _ => return None,
+        })
+    }
+}
+
+/// A namespace, identified by its inode on nsfs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Namespace {
+    /// Kind of namespace
+    pub kind: NamespaceKind,
+    /// Inode on nsfs, unique per namespace
+    pub inode: u64,
+}
+
+impl Namespace {
+    /// Parse a link such as `net:[4026531840]`
+    pub fn parse(link: &str) -> Option<Self> {
+        let (kind, inode) = link.split_once(":[")?;
+        Some(Namespace {
+            kind: NamespaceKind::from_name(kind)?,
+            inode: inode.strip_suffix(']')?.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:[{}]", self.kind.name(), self.inode)
+    }
+}
+
+impl FdEntry {
+    /// Get the namespace the descriptor refers to, if it is an nsfs file
+    pub fn namespace(&self) -> Option<Namespace> {
+        Namespace::parse(&self.link)
+    }
+}
+
+/// A descriptor of a process referring to a namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceHolder {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// Descriptor number
+    pub fd: u32,
+}
+
+/// A bind mount of a namespace, such as `/run/netns/<name>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceMount {
+    /// A process in the mount namespace holding the bind mount
+    pub pid: String,
+    /// Mount point, as seen by that process
+    pub mount_point: PathBuf,
+}
+
+/// A namespace no process is in, kept alive by descriptors or bind mounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedNamespace {
+    /// The namespace
+    pub namespace: Namespace,
+    /// Descriptors referring to the namespace, sorted by pid and fd
+    pub holders: Vec<NamespaceHolder>,
+    /// Bind mounts of the namespace
+    pub mounts: Vec<NamespaceMount>,
+}
+
+/// What a single process contributes to the report
+struct ProcessNamespaces {
+    active: Vec<Namespace>,
+    holders: Vec<(Namespace, NamespaceHolder)>,
+    mounts: Vec<(Namespace, NamespaceMount)>,
+}
+
+/// Get the bind mounts of namespaces from the contents of a mountinfo file
+///
+/// A namespace bind mount is on nsfs, with the namespace as its root.
+fn nsfs_mounts(content: &str) -> Vec<(Namespace, PathBuf)> {
+    mountinfo::parse(content)
+        .into_iter()
+        .filter(|mount| mount.fs_type == "nsfs")
+        .filter_map(|mount| Some((Namespace::parse(&mount.root)?, mount.mount_point)))
+        .collect()
+}
+
+impl LsofData {
+    /// Get the namespaces that no process is in but that are kept alive by
+    /// descriptors or bind mounts, with what pins them
+    ///
+    /// These are typically namespaces left over from containers that exited,
+    /// or network namespaces created with `ip netns add`. A namespace counts
+    /// as in use if it is in any `/proc/<pid>/ns` link, so processes whose
+    /// namespaces cannot be read can make one look pinned; check the
+    /// diagnostics when the report must be exact. Bind mounts are read once
+    /// per mount namespace.
+    pub fn namespace_ls(&mut self) -> Result<Vec<PinnedNamespace>> {
+        self.target_filetype = None;
+        self.target_filename = String::new();
+
+        let mut mount_namespaces = HashSet::new();
+        let processes = self.visit_processes(|data, pid, process, diagnostics| {
+            let info = data.scan_process(pid, process, diagnostics)?;
+            let holders = info
+                .fds
+                .iter()
+                .filter_map(|entry| {
+                    let holder = NamespaceHolder { pid: info.pid.clone(), name: info.name.clone(), fd: entry.fd };
+                    Some((entry.namespace()?, holder))
+                })
+                .collect();
+
+            let mut active = Vec::new();
+            match process.read_dir("ns") {
+                Ok(names) => {
+                    for name in names {
+                        let name = format!("ns/{}", name.to_string_lossy());
+                        match process.read_link(&name) {
+                            Ok(link) => active.extend(Namespace::parse(&link.to_string_lossy())),
+                            Err(err) => diagnostics.unreadable(format!("/proc/{}/{}", pid, name), &err),
+                        }
+                    }
+                }
+                Err(err) => diagnostics.unreadable(format!("/proc/{}/ns", pid), &err),
+            }
+
+            let mut mounts = Vec::new();
+            let mount_ns = active.iter().find(|ns| ns.kind == NamespaceKind::Mnt).copied();
+            if mount_ns.is_some_and(|ns| mount_namespaces.insert(ns)) {
+                match process.read_to_string("mountinfo") {
+                    Ok(content) => mounts.extend(nsfs_mounts(&content).into_iter().map(|(ns, mount_point)| {
+                        (ns, NamespaceMount { pid: pid.to_string(), mount_point })
+                    })),
+                    Err(err) => diagnostics.unreadable(format!("/proc/{}/mountinfo", pid), &err),
+                }
+            }
+            Ok(ProcessNamespaces { active, holders, mounts })
+        })?;
+
+        let active: HashSet<Namespace> = processes.iter().flat_map(|p| p.active.iter().copied()).collect();
+        let mut pinned: BTreeMap<Namespace, PinnedNamespace> = BTreeMap::new();
+        fn entry(pinned: &mut BTreeMap<Namespace, PinnedNamespace>, namespace: Namespace) -> &mut PinnedNamespace {
+            pinned.entry(namespace).or_insert_with(|| PinnedNamespace { namespace, holders: Vec::new(), mounts: Vec::new() })
+        }
+        for process in processes {
+            for (namespace, holder) in process.holders.into_iter().filter(|(ns, _)| !active.contains(ns)) {
+                entry(&mut pinned, namespace).holders.push(holder);
+            }
+            for (namespace, mount) in process.mounts.into_iter().filter(|(ns, _)| !active.contains(ns)) {
+                entry(&mut pinned, namespace).mounts.push(mount);
+            }
+        }
+
+        let mut pinned: Vec<PinnedNamespace> = pinned.into_values().collect();
+        for namespace in &mut pinned {
+            namespace.holders.sort_by_key(|h| (h.pid.parse::<u64>().unwrap_or(u64::MAX), h.fd));
+        }
+        Ok(pinned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::os::fd::AsRawFd;
+    use std::process::Command;
+
+    #[test]
+    fn test_parse_namespaces() {
+        let ns = Namespace::parse("net:[4026532288]").unwrap();
+        assert_eq!((ns.kind, ns.inode), (NamespaceKind::Net, 4026532288));
+        assert_eq!(ns.to_string(), "net:[4026532288]");
+        assert_eq!(Namespace::parse("socket:[1234]"), None);
+        assert_eq!(Namespace::parse("/dev/null"), None);
+
+        let content = "\
+28 1 254:0 / / rw,relatime - ext4 /dev/vda rw
+612 28 0:4 net:[4026532288] /run/netns/blue rw shared:5 - nsfs nsfs rw
+613 28 0:4 mnt:[4026532290] /run/pinned\\040mnt rw - nsfs nsfs rw";
+        let mounts = nsfs_mounts(content);
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0], (ns, PathBuf::from("/run/netns/blue")));
+        assert_eq!(mounts[1].0.kind, NamespaceKind::Mnt);
+        assert_eq!(mounts[1].1, PathBuf::from("/run/pinned mnt"));
+    }
+
+    #[test]
+    fn test_namespace_ls() {
+        // A network namespace whose only process exits while we hold it open
+        let Ok(mut child) = Command::new("unshare").args(["--net", "sleep", "30"]).spawn() else {
+            return;
+        };
+        let ours = std::fs::read_link("/proc/self/ns/net").unwrap();
+        let path = format!("/proc/{}/ns/net", child.id());
+        let mut file = None;
+        for _ in 0..100 {
+            if std::fs::read_link(&path).is_ok_and(|link| link != ours) {
+                file = File::open(&path).ok();
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let Some(file) = file else {
+            // unshare was not permitted
+            return;
+        };
+
+        let mut d = LsofData::new();
+        let pinned = d.namespace_ls().unwrap();
+        let me = std::process::id().to_string();
+        let ns = pinned
+            .iter()
+            .find(|p| p.holders.iter().any(|h| h.pid == me && h.fd == file.as_raw_fd() as u32))
+            .expect("pinned namespace not found");
+        assert_eq!(ns.namespace.kind, NamespaceKind::Net);
+        // Namespaces with processes in them are never reported
+        let ours = Namespace::parse(&ours.to_string_lossy()).unwrap();
+        assert!(pinned.iter().all(|p| p.namespace != ours));
+    }
+}
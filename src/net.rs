@@ -0,0 +1,198 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// A socket table under `/proc/net`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    /// `/proc/net/tcp`
+    Tcp,
+    /// `/proc/net/tcp6`
+    Tcp6,
+    /// `/proc/net/udp`
+    Udp,
+    /// `/proc/net/udp6`
+    Udp6,
+}
+
+impl Protocol {
+    /// Every table, in the order they are read
+    pub const ALL: [Protocol; 4] = [Protocol::Tcp, Protocol::Tcp6, Protocol::Udp, Protocol::Udp6];
+
+    /// Name of the table file
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Tcp6 => "tcp6",
+            Protocol::Udp => "udp",
+            Protocol::Udp6 => "udp6",
+        }
+    }
+
+    /// Whether this is one of the TCP tables
+    pub fn is_tcp(&self) -> bool {
+        matches!(self, Protocol::Tcp | Protocol::Tcp6)
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.file_name())
+    }
+}
+
+/// State of a socket, using the TCP state numbers the kernel prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SocketState {
+    /// Connected
+    Established,
+    /// Connection request sent
+    SynSent,
+    /// Connection request received
+    SynRecv,
+    /// Closed locally, waiting for the remote end
+    FinWait1,
+    /// Closed locally and acknowledged, waiting for the remote end
+    FinWait2,
+    /// Closed, waiting for stray packets to expire
+    TimeWait,
+    /// Closed, and for UDP also unconnected
+    Close,
+    /// Closed by the remote end, waiting for the local end
+    CloseWait,
+    /// Closed by both ends, waiting for the last acknowledgement
+    LastAck,
+    /// Listening for connections
+    Listen,
+    /// Both ends closing at the same time
+    Closing,
+    /// Connection request received, tracked without a full socket
+    NewSynRecv,
+    /// A state this parser does not know
+    Unknown(u8),
+}
+
+impl SocketState {
+    /// Convert a state number from the `st` column
+    fn from_raw(raw: u8) -> Self {
+        match raw {
+            0x01 => SocketState::Established,
+            0x02 => SocketState::SynSent,
+            0x03 => SocketState::SynRecv,
+            0x04 => SocketState::FinWait1,
+            0x05 => SocketState::FinWait2,
+            0x06 => SocketState::TimeWait,
+            0x07 => SocketState::Close,
+            0x08 => SocketState::CloseWait,
+            0x09 => SocketState::LastAck,
+            0x0A => SocketState::Listen,
+            0x0B => SocketState::Closing,
+            0x0C => SocketState::NewSynRecv,
+            other => SocketState::Unknown(other),
+        }
+    }
+}
+
+/// A single line of a `/proc/net/{tcp,tcp6,udp,udp6}` table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InetSocket {
+    /// Table the socket was found in
+    pub protocol: Protocol,
+    /// Local address and port
+    pub local: SocketAddr,
+    /// Remote address and port, unspecified for listening and unconnected sockets
+    pub remote: SocketAddr,
+    /// Socket state
+    pub state: SocketState,
+    /// Owner of the socket
+    pub uid: u32,
+    /// Inode of the socket, as in `socket:[inode]`
+    pub inode: u64,
+}
+
+/// Decode an address such as `0100007F:1F90`
+///
+/// The kernel prints the address as 32-bit words in host byte order and
+/// the port in hex.
+fn parse_addr(field: &str) -> Option<SocketAddr> {
+    let (addr, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..addr.len()).step_by(8) {
+        let word = u32::from_str_radix(addr.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Parse one table line
+fn parse_line(line: &str, protocol: Protocol) -> Option<InetSocket> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 10 {
+        return None;
+    }
+    Some(InetSocket {
+        protocol,
+        local: parse_addr(fields[1])?,
+        remote: parse_addr(fields[2])?,
+        state: SocketState::from_raw(u8::from_str_radix(fields[3], 16).ok()?),
+        uid: fields[7].parse().ok()?,
+        inode: fields[9].parse().ok()?,
+    })
+}
+
+/// Parse the contents of a socket table, skipping the header and malformed lines
+pub fn parse(content: &str, protocol: Protocol) -> Vec<InetSocket> {
+    content.lines().skip(1).filter_map(|line| parse_line(line, protocol)).collect()
+}
+
+/// Turn IPv4-mapped IPv6 addresses into plain IPv4
+///
+/// A dual-stack socket reports `::ffff:127.0.0.1` where its IPv4 peer
+/// reports `127.0.0.1`, so addresses are compared in this form.
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(IpAddr::V4(ip), addr.port()),
+            None => addr,
+        },
+        IpAddr::V4(_) => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // The fixtures are words printed by a little-endian kernel
+    #[cfg(target_endian = "little")]
+    fn test_parse_tables() {
+        let tcp = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 31337 1 0 100 0 0 10 0
+   1: 0100007F:1F90 0100007F:D431 01 00000000:00000000 00:00000000 00000000  1000        0 31338 1 0 20 4 30 10 -1";
+        let sockets = parse(tcp, Protocol::Tcp);
+        assert_eq!(sockets.len(), 2);
+        assert_eq!(sockets[0].local, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(sockets[0].state, SocketState::Listen);
+        assert_eq!(sockets[0].uid, 1000);
+        assert_eq!(sockets[1].remote, "127.0.0.1:54321".parse().unwrap());
+        assert_eq!(sockets[1].state, SocketState::Established);
+        assert_eq!(sockets[1].inode, 31338);
+
+        let tcp6 = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:0016 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 4242 1 0 100 0 0 10 0
+   1: 0000000000000000FFFF00000100007F:1F90 0000000000000000FFFF00000100007F:D432 01 00000000:00000000 00:00000000 00000000     0        0 4243 1 0 100 0 0 10 0";
+        let sockets = parse(tcp6, Protocol::Tcp6);
+        assert_eq!(sockets[0].local, "[::1]:22".parse().unwrap());
+        assert_eq!(canonical(sockets[1].local), "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(canonical(sockets[1].remote), "127.0.0.1:54322".parse().unwrap());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::fdinfo::{FanotifyTarget, FdDetail};
+use crate::mountinfo;
+use crate::{Error, LsofData, Result};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
+
+/// How a process watches a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+    /// An inotify watch on the file itself
+    Inotify {
+        /// Watch descriptor
+        wd: i32,
+    },
+    /// A fanotify mark on the file itself
+    FanotifyInode,
+    /// A fanotify mark on the mount the file is reached through
+    FanotifyMount,
+    /// A fanotify mark on the filesystem holding the file
+    FanotifyFilesystem,
+}
+
+/// A process watching a file through inotify or fanotify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watcher {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// Descriptor of the inotify or fanotify instance
+    pub fd: u32,
+    /// How the file is watched
+    pub kind: WatchKind,
+    /// Events watched for
+    pub mask: u32,
+    /// Events ignored
+    pub ignored_mask: u32,
+}
+
+/// Identity of the watched file: device, inode and mount ID
+struct Target {
+    device: (u32, u32),
+    inode: u64,
+    mount: Option<u32>,
+}
+
+impl Target {
+    /// Stat `path` without opening it for I/O, so FIFOs and devices are safe
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).custom_flags(libc::O_PATH).open(path)?;
+        let meta = file.metadata()?;
+        let fdinfo = fs::read_to_string(format!("/proc/self/fdinfo/{}", file.as_raw_fd()))?;
+        Ok(Target {
+            device: mountinfo::dev_major_minor(meta.dev()),
+            inode: meta.ino(),
+            mount: fdinfo.lines().find_map(|line| line.strip_prefix("mnt_id:")?.trim().parse().ok()),
+        })
+    }
+
+    /// Whether a fanotify mark covers the file
+    fn marked_by(&self, target: &FanotifyTarget) -> Option<WatchKind> {
+        match *target {
+            FanotifyTarget::Inode { inode, device } => {
+                (inode == self.inode && device == self.device).then_some(WatchKind::FanotifyInode)
+            }
+            FanotifyTarget::Mount(mount) => (Some(mount) == self.mount).then_some(WatchKind::FanotifyMount),
+            FanotifyTarget::Filesystem(device) => (device == self.device).then_some(WatchKind::FanotifyFilesystem),
+        }
+    }
+}
+
+impl LsofData {
+    /// Get the processes watching a file or directory through inotify or
+    /// fanotify
+    ///
+    /// Watches are matched by device and inode, so only watches on `path`
+    /// itself count, not those on its parent directory. Fanotify marks on
+    /// the mount or filesystem holding `path` count too.
+    pub fn watchers_ls(&mut self, path: impl AsRef<Path>) -> Result<Vec<Watcher>> {
+        let path = path.as_ref();
+        let target = Target::open(path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => Error::TargetNotFound(path.to_path_buf()),
+            _ => Error::Io(err),
+        })?;
+        self.target_filetype = None;
+        self.target_filename = String::new();
+
+        let watchers = self.visit_processes(|data, pid, process, diagnostics| {
+            let info = data.scan_process(pid, process, diagnostics)?;
+            let mut found = Vec::new();
+            for entry in &info.fds {
+                let watcher = |kind, mask, ignored_mask| Watcher {
+                    pid: info.pid.clone(),
+                    name: info.name.clone(),
+                    fd: entry.fd,
+                    kind,
+                    mask,
+                    ignored_mask,
+                };
+                match &entry.detail {
+                    Some(FdDetail::Inotify(watches)) => found.extend(
+                        watches
+                            .iter()
+                            .filter(|watch| watch.inode == target.inode && watch.device == target.device)
+                            .map(|watch| watcher(WatchKind::Inotify { wd: watch.wd }, watch.mask, watch.ignored_mask)),
+                    ),
+                    Some(FdDetail::Fanotify(marks)) => found.extend(marks.iter().filter_map(|mark| {
+                        Some(watcher(target.marked_by(&mark.target)?, mark.mask, mark.ignored_mask))
+                    })),
+                    _ => {}
+                }
+            }
+            Ok(found)
+        })?;
+        Ok(watchers.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::owned_fd;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn test_watchers_ls() {
+        let dir = std::env::temp_dir().join(format!("minilsof-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let c_dir = CString::new(dir.as_os_str().as_bytes()).unwrap();
+        // SAFETY: inotify_init1 takes no pointers and returns a new fd or -1
+        let inotify = owned_fd(unsafe { libc::inotify_init1(libc::IN_CLOEXEC) }).unwrap();
+        // SAFETY: `c_dir` is a valid C string
+        let wd = unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), c_dir.as_ptr(), libc::IN_CREATE | libc::IN_DELETE) };
+        assert!(wd >= 0);
+
+        let mut d = LsofData::new();
+        let watchers = d.watchers_ls(&dir).unwrap();
+        let me = std::process::id().to_string();
+        let watcher = watchers
+            .iter()
+            .find(|w| w.pid == me && w.fd == inotify.as_raw_fd() as u32)
+            .expect("inotify watch not found");
+        assert_eq!(watcher.kind, WatchKind::Inotify { wd });
+        assert_eq!(watcher.mask & libc::IN_CREATE, libc::IN_CREATE);
+
+        // A watch on the directory is not a watch on its entries
+        let file = dir.join("file");
+        fs::write(&file, b"").unwrap();
+        assert!(d.watchers_ls(&file).unwrap().iter().all(|w| w.pid != me));
+
+        drop(inotify);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(d.watchers_ls(&dir), Err(Error::TargetNotFound(_))));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::mountinfo;
+use crate::{Error, Result};
+use std::fmt;
+use std::fs::{self, read_to_string};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Capability bit of CAP_DAC_READ_SEARCH
+const CAP_DAC_READ_SEARCH: u32 = 2;
+/// Capability bit of CAP_SYS_PTRACE
+const CAP_SYS_PTRACE: u32 = 19;
+
+/// Reason why scans will not see everything on the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Limitation {
+    /// Descriptors of other users' processes cannot be read
+    OtherUsersUnreadable,
+    /// `/proc` is mounted with this `hidepid=` value, hiding other users' processes
+    HiddenProcesses(String),
+    /// `/proc` is mounted with `subset=pid`, hiding `/proc/net` and the socket tables
+    SubsetPid,
+    /// The `sock_diag` netlink interface failed, so unix socket peers are unknown
+    SockDiagUnavailable,
+}
+
+impl fmt::Display for Limitation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limitation::OtherUsersUnreadable => write!(f, "descriptors of other users' processes are unreadable"),
+            Limitation::HiddenProcesses(mode) => write!(f, "/proc is mounted with hidepid={}", mode),
+            Limitation::SubsetPid => write!(f, "/proc is mounted with subset=pid"),
+            Limitation::SockDiagUnavailable => write!(f, "sock_diag is unavailable, so unix socket peers are unknown"),
+        }
+    }
+}
+
+/// What the calling process can see through `/proc`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Visibility {
+    /// Effective user ID
+    pub euid: u32,
+    /// Whether CAP_SYS_PTRACE is in the effective set
+    pub cap_sys_ptrace: bool,
+    /// Whether CAP_DAC_READ_SEARCH is in the effective set
+    pub cap_dac_read_search: bool,
+    /// `hidepid=` option of the proc mount, if set
+    pub hidepid: Option<String>,
+    /// Whether the proc mount uses `subset=pid`
+    pub subset_pid: bool,
+    /// Whether `/proc/<pid>/fd` of another user's process could be listed,
+    /// or `None` if no such process was visible to try
+    pub other_users_fd_readable: Option<bool>,
+}
+
+impl Visibility {
+    /// Reasons why scans will miss processes or sockets
+    pub fn limitations(&self) -> Vec<Limitation> {
+        let mut limitations = Vec::new();
+        if self.other_users_fd_readable == Some(false) {
+            limitations.push(Limitation::OtherUsersUnreadable);
+        }
+        if let Some(mode) = &self.hidepid {
+            // hidepid=0 and hidepid=off hide nothing; ptrace access sees through the rest
+            if mode != "0" && mode != "off" && !self.cap_sys_ptrace {
+                limitations.push(Limitation::HiddenProcesses(mode.clone()));
+            }
+        }
+        if self.subset_pid {
+            limitations.push(Limitation::SubsetPid);
+        }
+        limitations
+    }
+
+    /// Whether scans will see every process and socket on the host
+    pub fn is_complete(&self) -> bool {
+        self.limitations().is_empty()
+    }
+}
+
+/// Parse the `Uid:` and `CapEff:` lines of `/proc/self/status`
+fn parse_status(status: &str) -> Option<(u32, u64)> {
+    let mut euid = None;
+    let mut cap_eff = None;
+    for line in status.lines() {
+        if let Some(value) = line.strip_prefix("Uid:") {
+            // real, effective, saved, filesystem
+            euid = value.split_whitespace().nth(1)?.parse().ok();
+        } else if let Some(value) = line.strip_prefix("CapEff:") {
+            cap_eff = u64::from_str_radix(value.trim(), 16).ok();
+        }
+    }
+    Some((euid?, cap_eff?))
+}
+
+/// Try to list the fd directory of some process owned by another user
+fn other_users_fd_readable(euid: u32) -> Option<bool> {
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let name = entry.file_name();
+        if name.to_str().and_then(|n| n.parse::<u32>().ok()).is_none() {
+            continue;
+        }
+        match entry.metadata() {
+            Ok(meta) if meta.uid() != euid => {}
+            _ => continue,
+        }
+        match fs::read_dir(entry.path().join("fd")) {
+            Ok(_) => return Some(true),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => return Some(false),
+            // The process exited; try another one
+            Err(_) => continue,
+        }
+    }
+    None
+}
+
+/// Work out up front how complete scans of `/proc` will be
+pub fn probe() -> Result<Visibility> {
+    let status = read_to_string("/proc/self/status").map_err(|_| Error::ProcfsUnavailable)?;
+    let (euid, cap_eff) = parse_status(&status).ok_or(Error::ProcfsUnavailable)?;
+
+    let proc_mount = mountinfo::read("self")?
+        .into_iter()
+        .rev()
+        .find(|mount| mount.fs_type == "proc" && mount.mount_point == Path::new("/proc"));
+    let hidepid = proc_mount.as_ref().and_then(|m| m.option("hidepid")).map(str::to_string);
+    let subset_pid = proc_mount.as_ref().and_then(|m| m.option("subset")) == Some("pid");
+
+    Ok(Visibility {
+        euid,
+        cap_sys_ptrace: cap_eff & (1 << CAP_SYS_PTRACE) != 0,
+        cap_dac_read_search: cap_eff & (1 << CAP_DAC_READ_SEARCH) != 0,
+        hidepid,
+        subset_pid,
+        other_users_fd_readable: other_users_fd_readable(euid),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let status = "Name:\tcat\nUid:\t1000\t0\t0\t0\nCapEff:\t0000000000080004\n";
+        assert_eq!(parse_status(status), Some((0, 0x80004)));
+    }
+
+    #[test]
+    fn test_limitations() {
+        let visibility = Visibility {
+            euid: 1000,
+            cap_sys_ptrace: false,
+            cap_dac_read_search: false,
+            hidepid: Some("invisible".to_string()),
+            subset_pid: true,
+            other_users_fd_readable: Some(false),
+        };
+        assert_eq!(
+            visibility.limitations(),
+            [
+                Limitation::OtherUsersUnreadable,
+                Limitation::HiddenProcesses("invisible".to_string()),
+                Limitation::SubsetPid,
+            ]
+        );
+
+        let root = Visibility {
+            euid: 0,
+            cap_sys_ptrace: true,
+            cap_dac_read_search: true,
+            hidepid: Some("2".to_string()),
+            subset_pid: false,
+            other_users_fd_readable: Some(true),
+        };
+        assert!(root.is_complete());
+        assert!(probe().is_ok());
+    }
+}
@@ -0,0 +1,264 @@
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::PathBuf;
+
+/// Convert a path component to a C string
+fn c_name(name: &str) -> io::Result<CString> {
+    CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+/// Wrap the return value of a call that yields a new fd
+pub(crate) fn owned_fd(fd: libc::c_int) -> io::Result<OwnedFd> {
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just returned by the kernel and is owned by no one else
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// The parts of `struct stat` the scanner uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStat {
+    /// Device holding the file, in userspace encoding
+    pub(crate) dev: u64,
+    /// Inode number
+    pub(crate) ino: u64,
+    /// File type and permission bits
+    pub(crate) mode: u32,
+    /// Number of hard links
+    pub(crate) nlink: u64,
+    /// Size in bytes
+    pub(crate) size: u64,
+    /// Allocated size in 512-byte blocks
+    pub(crate) blocks: u64,
+}
+
+impl FileStat {
+    /// Convert the raw result of a stat call
+    pub(crate) fn from_raw(st: &libc::stat) -> Self {
+        Self {
+            dev: st.st_dev,
+            ino: st.st_ino,
+            mode: st.st_mode,
+            nlink: st.st_nlink,
+            size: st.st_size as u64,
+            blocks: st.st_blocks as u64,
+        }
+    }
+
+    /// Whether this is a regular file
+    pub(crate) fn is_file(&self) -> bool {
+        self.mode & libc::S_IFMT == libc::S_IFREG
+    }
+}
+
+/// A process opened once, so that every read refers to the same instance
+///
+/// All reads go through a directory fd on `/proc/<pid>`, which keeps
+/// pointing at the original process even if the pid is reused; reads fail
+/// with ESRCH once it has exited. The pidfd is opened before the directory,
+/// so while [`ProcessHandle::is_alive`] holds the pid cannot have been
+/// recycled in between and both fds name the same process.
+pub(crate) struct ProcessHandle {
+    /// Process ID
+    pid: libc::pid_t,
+    /// `/proc/<pid>` directory
+    dir: OwnedFd,
+    /// pidfd of the process, if the kernel supports them
+    pidfd: Option<OwnedFd>,
+}
+
+impl ProcessHandle {
+    /// Open process `pid`
+    pub(crate) fn open(pid: &str) -> io::Result<Self> {
+        let number: libc::pid_t = pid.parse().map_err(|_| io::Error::from(io::ErrorKind::NotFound))?;
+
+        // SAFETY: pidfd_open takes a pid and flags and returns a new fd or -1
+        let pidfd = match owned_fd(unsafe { libc::syscall(libc::SYS_pidfd_open, number, 0) } as libc::c_int) {
+            Ok(pidfd) => Some(pidfd),
+            // Kernels before 5.3 have no pidfds; fall back to the dirfd alone
+            Err(err) if err.raw_os_error() == Some(libc::ENOSYS) => None,
+            Err(err) if err.raw_os_error() == Some(libc::ESRCH) => {
+                return Err(io::Error::from(io::ErrorKind::NotFound));
+            }
+            Err(err) => return Err(err),
+        };
+
+        let path = c_name(&format!("/proc/{}", pid))?;
+        // SAFETY: `path` is a valid NUL-terminated string
+        let dir = owned_fd(unsafe {
+            libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC)
+        })?;
+
+        Ok(Self { dir, pidfd, pid: number })
+    }
+
+    /// Descriptors the handle holds open in the process it refers to
+    ///
+    /// Empty unless the handle is on the calling process, whose fd listing
+    /// would otherwise show the handle itself.
+    pub(crate) fn own_fds(&self) -> Vec<u32> {
+        if self.pid != std::process::id() as libc::pid_t {
+            return Vec::new();
+        }
+        let mut fds = vec![self.dir.as_raw_fd() as u32];
+        fds.extend(self.pidfd.as_ref().map(|pidfd| pidfd.as_raw_fd() as u32));
+        fds
+    }
+
+    /// Open a file relative to the process directory
+    fn open_at(&self, name: &str, flags: libc::c_int) -> io::Result<OwnedFd> {
+        let name = c_name(name)?;
+        // SAFETY: `dir` is an open directory fd and `name` is NUL-terminated
+        owned_fd(unsafe { libc::openat(self.dir.as_raw_fd(), name.as_ptr(), flags | libc::O_CLOEXEC) })
+    }
+
+    /// Read a file such as `status` or `maps`
+    pub(crate) fn read_to_string(&self, name: &str) -> io::Result<String> {
+        let mut content = String::new();
+        File::from(self.open_at(name, libc::O_RDONLY)?).read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    /// Read a symbolic link such as `fd/3` or `exe`
+    pub(crate) fn read_link(&self, name: &str) -> io::Result<PathBuf> {
+        let name = c_name(name)?;
+        let mut buf = vec![0u8; 256];
+        loop {
+            // SAFETY: `buf` is valid for `buf.len()` bytes and `name` is NUL-terminated
+            let len = unsafe {
+                libc::readlinkat(self.dir.as_raw_fd(), name.as_ptr(), buf.as_mut_ptr().cast(), buf.len())
+            };
+            if len < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let len = len as usize;
+            if len < buf.len() {
+                buf.truncate(len);
+                return Ok(PathBuf::from(OsString::from_vec(buf)));
+            }
+            // The target may have been truncated; retry with more room
+            buf.resize(buf.len() * 2, 0);
+        }
+    }
+
+    /// Get the status of the file behind a path such as `fd/3`, following links
+    pub(crate) fn stat(&self, name: &str) -> io::Result<FileStat> {
+        let name = c_name(name)?;
+        let mut st = std::mem::MaybeUninit::<libc::stat>::uninit();
+        // SAFETY: `st` is valid for writes and `name` is NUL-terminated
+        if unsafe { libc::fstatat(self.dir.as_raw_fd(), name.as_ptr(), st.as_mut_ptr(), 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fstatat succeeded, so it filled in `st`
+        Ok(FileStat::from_raw(unsafe { &st.assume_init() }))
+    }
+
+    /// List the entry names of a directory such as `fd`
+    pub(crate) fn read_dir(&self, name: &str) -> io::Result<Vec<OsString>> {
+        let fd = self.open_at(name, libc::O_RDONLY | libc::O_DIRECTORY)?;
+        // SAFETY: fdopendir takes ownership of the fd on success
+        let dir = unsafe { libc::fdopendir(fd.as_raw_fd()) };
+        if dir.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let _ = fd.into_raw_fd();
+
+        let mut names = Vec::new();
+        let result = loop {
+            // readdir only sets errno on failure, so clear it first
+            // SAFETY: errno is thread-local
+            unsafe { *libc::__errno_location() = 0 };
+            // SAFETY: `dir` is an open directory stream
+            let entry = unsafe { libc::readdir(dir) };
+            if entry.is_null() {
+                let err = io::Error::last_os_error();
+                break if err.raw_os_error() == Some(0) { Ok(()) } else { Err(err) };
+            }
+            // SAFETY: `d_name` of a returned entry is a NUL-terminated string
+            let entry_name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            let entry_name = OsStr::from_bytes(entry_name.to_bytes());
+            if entry_name != "." && entry_name != ".." {
+                names.push(entry_name.to_os_string());
+            }
+        };
+        // SAFETY: `dir` is open and not used after this
+        unsafe { libc::closedir(dir) };
+
+        result.map(|_| names)
+    }
+
+    /// Whether the process opened by [`ProcessHandle::open`] still exists
+    ///
+    /// Signal 0 fails with EPERM for processes the caller may not signal,
+    /// which are still alive; only ESRCH means the process is gone. Without
+    /// pidfd support this checks that the process directory can still be
+    /// read, which fails once the process has been reaped.
+    pub(crate) fn is_alive(&self) -> bool {
+        match &self.pidfd {
+            Some(pidfd) => {
+                // SAFETY: signal 0 only checks for existence; no info, no flags
+                let sent = unsafe {
+                    libc::syscall(
+                        libc::SYS_pidfd_send_signal,
+                        pidfd.as_raw_fd(),
+                        0,
+                        std::ptr::null::<libc::siginfo_t>(),
+                        0,
+                    )
+                };
+                signal_reached(sent, io::Error::last_os_error().raw_os_error())
+            }
+            None => self.read_to_string("stat").is_ok(),
+        }
+    }
+}
+
+/// Whether a signal-0 probe that returned `sent`, failing with `errno`,
+/// found the process
+///
+/// EPERM means the caller may not signal the process, which still exists;
+/// only ESRCH means it is gone.
+fn signal_reached(sent: libc::c_long, errno: Option<i32>) -> bool {
+    sent == 0 || errno != Some(libc::ESRCH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_handle() {
+        let me = ProcessHandle::open(&std::process::id().to_string()).unwrap();
+        assert!(me.is_alive());
+        assert!(me.read_to_string("status").unwrap().starts_with("Name:"));
+
+        let fds = me.read_dir("fd").unwrap();
+        assert!(fds.iter().any(|fd| fd == "0"));
+        assert!(me.read_link("exe").unwrap().is_absolute());
+    }
+
+    #[test]
+    fn test_process_handle_outlived() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let handle = ProcessHandle::open(&child.id().to_string());
+        child.wait().unwrap();
+
+        // Once reaped, reads through the old handle must not reach a new process
+        if let Ok(handle) = handle {
+            assert!(!handle.is_alive());
+            assert!(handle.read_to_string("status").is_err());
+        }
+    }
+
+    #[test]
+    fn test_signal_reached() {
+        assert!(signal_reached(0, None));
+        // Another user's process, which we may not signal
+        assert!(signal_reached(-1, Some(libc::EPERM)));
+        assert!(!signal_reached(-1, Some(libc::ESRCH)));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::procfs::ProcessHandle;
+use crate::{Diagnostics, Error, Fdinfo, LsofData, LsofFiletype, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+/// An answer served by a [`Snapshot`], together with the age of its data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cached<T> {
+    /// The query result
+    pub value: T,
+    /// Time elapsed since the scan the result was computed from
+    pub age: Duration,
+    /// Wall-clock time of that scan
+    pub scanned_at: SystemTime,
+    /// Parts of that scan that could not be read
+    pub diagnostics: Diagnostics,
+}
+
+/// What a refresh had to do to bring a snapshot up to date
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshStats {
+    /// Processes that were new or whose descriptors changed
+    pub rescanned: usize,
+    /// Processes kept from the previous pass as-is
+    pub unchanged: usize,
+    /// Processes that exited since the previous pass
+    pub dropped: usize,
+}
+
+/// Cheap per-process state used to decide whether a rescan is needed
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fingerprint {
+    /// Start time, which changes when the pid is reused
+    start_time: Option<u64>,
+    /// Sorted descriptors listed in `/proc/<pid>/fd`, with their link targets
+    fds: Vec<(u32, String)>,
+    /// Sorted paths of the files mapped in `/proc/<pid>/maps`
+    maps: Vec<String>,
+}
+
+/// A reusable scan of `/proc` with a time-to-live
+///
+/// Queries are answered from the cached scan while it is younger than the
+/// TTL. Once it expires the next query refreshes it incrementally: only
+/// processes that appeared, or whose descriptors or mapped files point
+/// somewhere else, are read again, and processes that exited are dropped.
+pub struct Snapshot {
+    /// Scanner used to read individual processes
+    scanner: LsofData,
+    /// How long a scan may be reused
+    ttl: Duration,
+    /// Map of process IDs to their file descriptor info
+    processes: HashMap<String, Fdinfo>,
+    /// Fingerprint of every process at the time it was last read
+    fingerprints: HashMap<String, Fingerprint>,
+    /// When the last refresh finished
+    refreshed: Option<(Instant, SystemTime)>,
+    /// Parts of the last refresh that could not be read
+    diagnostics: Diagnostics,
+}
+
+impl Snapshot {
+    /// Create an empty snapshot whose scans may be reused for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        let mut scanner = LsofData::new();
+        scanner.target_filetype = Some(LsofFiletype::All);
+        Self {
+            scanner,
+            ttl,
+            processes: HashMap::new(),
+            fingerprints: HashMap::new(),
+            refreshed: None,
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// How long a scan may be reused
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Age of the cached scan, or `None` before the first refresh
+    pub fn age(&self) -> Option<Duration> {
+        self.refreshed.map(|(at, _)| at.elapsed())
+    }
+
+    /// Parts of the last refresh that could not be read
+    ///
+    /// Processes that could not be read are retried on every refresh, so
+    /// they are always reported; unreadable files of processes kept from an
+    /// earlier pass are reported by the pass that read them.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Whether the next query will refresh the snapshot first
+    pub fn is_stale(&self) -> bool {
+        self.age().is_none_or(|age| age >= self.ttl)
+    }
+
+    /// Read the fingerprint of an opened process
+    ///
+    /// Descriptors are compared by link target, since a closed descriptor
+    /// is usually reused for the next file the process opens.
+    fn fingerprint(&self, process: &ProcessHandle) -> Fingerprint {
+        let own_fds = process.own_fds();
+        let mut fds: Vec<(u32, String)> = process
+            .read_dir("fd")
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| {
+                        let fd = name.to_str()?.parse().ok().filter(|fd| !own_fds.contains(fd))?;
+                        let link = process.read_link(&format!("fd/{}", fd)).ok()?;
+                        Some((fd, link.to_string_lossy().into_owned()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        fds.sort_unstable();
+        let mut maps = self.scanner.get_mem_info(process).unwrap_or_default();
+        maps.sort_unstable();
+        maps.dedup();
+
+        Fingerprint {
+            start_time: process.read_to_string("stat").ok().as_deref().and_then(LsofData::parse_start_time),
+            fds,
+            maps,
+        }
+    }
+
+    /// Bring the snapshot up to date, rescanning only what changed
+    pub fn refresh(&mut self) -> Result<RefreshStats> {
+        self.update().map(|(stats, _)| stats)
+    }
+
+    /// Refresh and return the time the refresh finished
+    fn update(&mut self) -> Result<(RefreshStats, (Instant, SystemTime))> {
+        let mut stats = RefreshStats::default();
+        let mut diagnostics = Diagnostics::default();
+        let pids = self.scanner.proc_pids(&mut diagnostics)?;
+
+        let live: HashSet<&String> = pids.iter().collect();
+        let before = self.processes.len();
+        self.processes.retain(|pid, _| live.contains(pid));
+        self.fingerprints.retain(|pid, _| live.contains(pid));
+        stats.dropped = before - self.processes.len();
+
+        for pid in &pids {
+            // The fingerprint and the rescan are read through one handle, so
+            // both belong to the same process
+            let scanned = LsofData::read_process(pid, |process| {
+                let fingerprint = self.fingerprint(process);
+                if self.processes.contains_key(pid) && self.fingerprints.get(pid) == Some(&fingerprint) {
+                    return Ok(None);
+                }
+                let info = self.scanner.scan_process(pid, process, &mut diagnostics)?;
+                Ok(Some((info, fingerprint)))
+            });
+            match scanned {
+                Ok(None) => stats.unchanged += 1,
+                Ok(Some((info, fingerprint))) => {
+                    stats.rescanned += 1;
+                    self.processes.insert(pid.clone(), info);
+                    self.fingerprints.insert(pid.clone(), fingerprint);
+                }
+                // Processes that exited or cannot be read are left out
+                Err(err) => {
+                    stats.rescanned += 1;
+                    diagnostics.skipped(pid, err);
+                    self.processes.remove(pid);
+                    self.fingerprints.remove(pid);
+                }
+            }
+        }
+
+        let refreshed = (Instant::now(), SystemTime::now());
+        self.refreshed = Some(refreshed);
+        self.diagnostics = diagnostics;
+        Ok((stats, refreshed))
+    }
+
+    /// Discard the cached scan and read every process again
+    pub fn rescan(&mut self) -> Result<RefreshStats> {
+        self.processes.clear();
+        self.fingerprints.clear();
+        self.refresh()
+    }
+
+    /// Refresh if the TTL expired and return the time of the scan in use
+    fn ensure_fresh(&mut self) -> Result<(Instant, SystemTime)> {
+        match self.refreshed {
+            Some(refreshed) if refreshed.0.elapsed() < self.ttl => Ok(refreshed),
+            _ => self.update().map(|(_, refreshed)| refreshed),
+        }
+    }
+
+    /// Wrap a query result with the age of the scan it came from
+    fn cached<T>(&self, value: T, (at, scanned_at): (Instant, SystemTime)) -> Cached<T> {
+        Cached {
+            value,
+            age: at.elapsed(),
+            scanned_at,
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+
+    /// Get information about all open files by all processes
+    pub fn file_ls(&mut self) -> Result<Cached<&HashMap<String, Fdinfo>>> {
+        let refreshed = self.ensure_fresh()?;
+        Ok(self.cached(&self.processes, refreshed))
+    }
+
+    /// Get information about processes using a specific file
+    pub fn target_file_ls(&mut self, path: impl AsRef<Path>) -> Result<Cached<Vec<Fdinfo>>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(Error::TargetNotFound(path.to_path_buf()));
+        }
+        let target = path.to_string_lossy().to_string();
+
+        let refreshed = self.ensure_fresh()?;
+        let holders = self
+            .processes
+            .values()
+            .filter(|info| info.link.contains(&target))
+            .cloned()
+            .collect();
+        Ok(self.cached(holders, refreshed))
+    }
+
+    /// Get information about processes using a specific port
+    ///
+    /// The socket tables are always read live; only the process side of the
+    /// answer comes from the cache.
+    pub fn port_ls(&mut self, port: impl AsRef<str>) -> Result<Cached<Vec<Fdinfo>>> {
+        let port = LsofData::parse_port(port.as_ref())?;
+        let socket_inodes: HashSet<String> = self.scanner.get_port_info(port).into_iter().collect();
+
+        let refreshed = self.ensure_fresh()?;
+        let holders = self
+            .processes
+            .values()
+            .filter(|info| !info.link.is_disjoint(&socket_inodes))
+            .cloned()
+            .collect();
+        Ok(self.cached(holders, refreshed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn test_snapshot_reused_within_ttl() {
+        let mut snapshot = Snapshot::new(Duration::from_secs(3600));
+        let first = snapshot.file_ls().unwrap().scanned_at;
+        let second = snapshot.file_ls().unwrap();
+        assert_eq!(first, second.scanned_at);
+        assert!(!snapshot.is_stale());
+    }
+
+    #[test]
+    fn test_snapshot_incremental_refresh() {
+        let path = std::env::temp_dir().join(format!("minilsof-snapshot-{}", std::process::id()));
+        let me = std::process::id().to_string();
+        let mut snapshot = Snapshot::new(Duration::ZERO);
+        snapshot.refresh().unwrap();
+
+        let file = fs::File::create(&path).unwrap();
+        let stats = snapshot.refresh().unwrap();
+        assert!(stats.rescanned >= 1);
+        assert!(stats.unchanged >= 1);
+        let held = snapshot.target_file_ls(&path).unwrap();
+        assert!(held.value.iter().any(|info| info.pid == me));
+
+        drop(file);
+        let held = snapshot.target_file_ls(&path).unwrap();
+        assert!(held.value.iter().all(|info| info.pid != me));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_reused_fd_number() {
+        let first = std::env::temp_dir().join(format!("minilsof-snapshot-first-{}", std::process::id()));
+        let second = std::env::temp_dir().join(format!("minilsof-snapshot-second-{}", std::process::id()));
+        let me = std::process::id().to_string();
+        let mut snapshot = Snapshot::new(Duration::ZERO);
+
+        let file = fs::File::create(&first).unwrap();
+        assert!(snapshot.target_file_ls(&first).unwrap().value.iter().any(|info| info.pid == me));
+        // The kernel hands out the lowest free number, usually the one just closed
+        drop(file);
+        let file = fs::File::create(&second).unwrap();
+        let fd = file.as_raw_fd() as u32;
+        let all = snapshot.file_ls().unwrap();
+        assert!(all.value[&me].fds.iter().any(|entry| entry.fd == fd && entry.link == second.to_string_lossy()));
+        assert!(snapshot.target_file_ls(&first).unwrap().value.iter().all(|info| info.pid != me));
+
+        drop(file);
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+}
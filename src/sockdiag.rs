@@ -0,0 +1,198 @@
+use crate::ipc::{self, UnixSocket, UnixSocketType};
+use crate::procfs::owned_fd;
+use std::io;
+use std::os::fd::AsRawFd;
+
+/// `sock_diag` message type for per-family dumps
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+/// Ask for the bound name of each socket
+const UDIAG_SHOW_NAME: u32 = 0x1;
+/// Ask for the inode of each socket's peer
+const UDIAG_SHOW_PEER: u32 = 0x4;
+/// Attribute carrying the bound name
+const UNIX_DIAG_NAME: u16 = 0;
+/// Attribute carrying the peer inode
+const UNIX_DIAG_PEER: u16 = 2;
+/// Socket state of a listening socket
+const TCP_LISTEN: u8 = 10;
+/// Size of `struct nlmsghdr`
+const NLMSG_HDRLEN: usize = 16;
+/// Size of `struct unix_diag_msg`
+const UNIX_DIAG_MSG_LEN: usize = 16;
+
+/// Round up to the 4-byte alignment of netlink messages and attributes
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Read a native-endian u16 at `offset`
+fn u16_at(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_ne_bytes(buf.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+/// Read a native-endian u32 at `offset`
+fn u32_at(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(buf.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Build a dump request for every unix socket with its name and peer
+fn dump_request() -> Vec<u8> {
+    let mut req = Vec::with_capacity(NLMSG_HDRLEN + 24);
+    // struct nlmsghdr
+    req.extend_from_slice(&((NLMSG_HDRLEN + 24) as u32).to_ne_bytes());
+    req.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+    req.extend_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+    req.extend_from_slice(&1u32.to_ne_bytes());
+    req.extend_from_slice(&0u32.to_ne_bytes());
+    // struct unix_diag_req
+    req.push(libc::AF_UNIX as u8);
+    req.push(0);
+    req.extend_from_slice(&0u16.to_ne_bytes());
+    req.extend_from_slice(&u32::MAX.to_ne_bytes());
+    req.extend_from_slice(&0u32.to_ne_bytes());
+    req.extend_from_slice(&(UDIAG_SHOW_NAME | UDIAG_SHOW_PEER).to_ne_bytes());
+    req.extend_from_slice(&[0u8; 8]);
+    req
+}
+
+/// Decode the payload of one `unix_diag_msg` with its attributes
+fn parse_message(payload: &[u8]) -> Option<UnixSocket> {
+    let mut socket = UnixSocket {
+        inode: u64::from(u32_at(payload, 4)?),
+        socket_type: UnixSocketType::from_raw(*payload.get(1)?),
+        listening: *payload.get(2)? == TCP_LISTEN,
+        path: None,
+        peer: None,
+    };
+
+    let mut offset = UNIX_DIAG_MSG_LEN;
+    while offset + 4 <= payload.len() {
+        let len = u16_at(payload, offset)? as usize;
+        let kind = u16_at(payload, offset + 2)?;
+        if len < 4 {
+            break;
+        }
+        let data = payload.get(offset + 4..offset + len)?;
+        match kind {
+            UNIX_DIAG_NAME => socket.path = Some(ipc::unix_path(data)),
+            UNIX_DIAG_PEER => socket.peer = u32_at(data, 0).map(u64::from).filter(|&peer| peer != 0),
+            _ => {}
+        }
+        offset += align(len);
+    }
+    Some(socket)
+}
+
+/// Decode a buffer of netlink messages
+///
+/// Returns `true` once the end of the dump has been reached.
+fn parse_messages(buf: &[u8], sockets: &mut Vec<UnixSocket>) -> io::Result<bool> {
+    let mut offset = 0;
+    while offset + NLMSG_HDRLEN <= buf.len() {
+        let invalid = || io::Error::from(io::ErrorKind::InvalidData);
+        let len = u32_at(buf, offset).ok_or_else(invalid)? as usize;
+        let kind = u16_at(buf, offset + 4).ok_or_else(invalid)?;
+        if len < NLMSG_HDRLEN || offset + len > buf.len() {
+            return Err(invalid());
+        }
+
+        match kind as libc::c_int {
+            libc::NLMSG_DONE => return Ok(true),
+            libc::NLMSG_ERROR => {
+                let errno = u32_at(buf, offset + NLMSG_HDRLEN).ok_or_else(invalid)? as i32;
+                if errno != 0 {
+                    return Err(io::Error::from_raw_os_error(-errno));
+                }
+            }
+            _ => sockets.extend(parse_message(&buf[offset + NLMSG_HDRLEN..offset + len])),
+        }
+        offset += align(len);
+    }
+    Ok(false)
+}
+
+/// Dump every unix socket in the caller's network namespace through the
+/// `sock_diag` netlink interface
+///
+/// Fails if the kernel was built without `unix_diag`.
+pub(crate) fn unix_sockets() -> io::Result<Vec<UnixSocket>> {
+    // SAFETY: socket takes no pointers and returns a new fd or -1
+    let socket = owned_fd(unsafe {
+        libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, libc::NETLINK_SOCK_DIAG)
+    })?;
+
+    let req = dump_request();
+    // SAFETY: `sockaddr_nl` is plain data, so all zeroes is a valid value
+    let mut kernel: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    // SAFETY: `req` and `kernel` are valid for the lengths passed
+    let sent = unsafe {
+        libc::sendto(
+            socket.as_raw_fd(),
+            req.as_ptr().cast(),
+            req.len(),
+            0,
+            (&kernel as *const libc::sockaddr_nl).cast(),
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut sockets = Vec::new();
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        // SAFETY: `buf` is valid for writes of `buf.len()` bytes
+        let len = unsafe { libc::recv(socket.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if len < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if len == 0 || parse_messages(&buf[..len as usize], &mut sockets)? {
+            return Ok(sockets);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages() {
+        // unix_diag_msg of a connected stream socket with a name and a peer
+        let mut payload = vec![libc::AF_UNIX as u8, 1, 1, 0];
+        payload.extend_from_slice(&4242u32.to_ne_bytes());
+        payload.extend_from_slice(&[0u8; 8]);
+        let name = b"/run/app.sock";
+        payload.extend_from_slice(&((4 + name.len()) as u16).to_ne_bytes());
+        payload.extend_from_slice(&UNIX_DIAG_NAME.to_ne_bytes());
+        payload.extend_from_slice(name);
+        payload.resize(align(payload.len()), 0);
+        payload.extend_from_slice(&8u16.to_ne_bytes());
+        payload.extend_from_slice(&UNIX_DIAG_PEER.to_ne_bytes());
+        payload.extend_from_slice(&4243u32.to_ne_bytes());
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&((NLMSG_HDRLEN + payload.len()) as u32).to_ne_bytes());
+        buf.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+        buf.extend_from_slice(&[0u8; 10]);
+        buf.extend_from_slice(&payload);
+        buf.extend_from_slice(&(NLMSG_HDRLEN as u32).to_ne_bytes());
+        buf.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+        buf.extend_from_slice(&[0u8; 10]);
+
+        let mut sockets = Vec::new();
+        assert!(parse_messages(&buf, &mut sockets).unwrap());
+        assert_eq!(sockets.len(), 1);
+        assert_eq!(sockets[0].inode, 4242);
+        assert_eq!(sockets[0].peer, Some(4243));
+        assert_eq!(sockets[0].path.as_deref(), Some("/run/app.sock"));
+        assert_eq!(sockets[0].socket_type, UnixSocketType::Stream);
+        assert!(!sockets[0].listening);
+    }
+}
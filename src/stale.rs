@@ -0,0 +1,279 @@
+use crate::maps::MapEntry;
+use crate::mountinfo;
+use crate::procfs::ProcessHandle;
+use crate::{Diagnostics, LsofData, Result};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Why a mapped file is out of date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StaleReason {
+    /// The file was unlinked, usually by a package upgrade
+    Deleted,
+    /// The path now refers to a different inode than the one in use
+    Replaced,
+}
+
+/// An executable or library that a process still uses in an old version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleFile {
+    /// Path of the file, without any ` (deleted)` suffix
+    pub path: PathBuf,
+    /// Device of the file in use, as `(major, minor)`
+    pub device: (u32, u32),
+    /// Inode of the file in use
+    pub inode: u64,
+    /// Why the file is out of date
+    pub reason: StaleReason,
+}
+
+/// A process that needs a restart to pick up new files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleProcess {
+    /// Process ID
+    pub pid: String,
+    /// Process name (if available)
+    pub name: Option<String>,
+    /// The executable, if it is out of date
+    pub exe: Option<StaleFile>,
+    /// Out of date shared libraries, sorted by path
+    pub libraries: Vec<StaleFile>,
+    /// systemd unit to restart, taken from the process's cgroup
+    pub unit: Option<String>,
+}
+
+/// An out of date library and the processes still mapping it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleLibrary {
+    /// Path of the library
+    pub path: PathBuf,
+    /// Processes mapping an old version, sorted by pid
+    pub pids: Vec<String>,
+}
+
+/// Processes running outdated executables or libraries
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct StaleReport {
+    /// Affected processes, sorted by pid
+    pub processes: Vec<StaleProcess>,
+    /// The same findings grouped by library, sorted by path
+    pub libraries: Vec<StaleLibrary>,
+    /// Units to restart, sorted and without duplicates
+    pub units: Vec<String>,
+}
+
+impl StaleReport {
+    /// Build the report from per-process findings
+    fn new(mut processes: Vec<StaleProcess>) -> Self {
+        processes.sort_by_key(|process| process.pid.parse::<u64>().unwrap_or(u64::MAX));
+
+        let mut libraries: BTreeMap<&Path, Vec<String>> = BTreeMap::new();
+        for process in &processes {
+            for library in &process.libraries {
+                libraries.entry(&library.path).or_default().push(process.pid.clone());
+            }
+        }
+        let libraries = libraries
+            .into_iter()
+            .map(|(path, pids)| StaleLibrary { path: path.to_path_buf(), pids })
+            .collect();
+        let units: BTreeSet<&String> = processes.iter().filter_map(|process| process.unit.as_ref()).collect();
+
+        Self {
+            units: units.into_iter().cloned().collect(),
+            libraries,
+            processes,
+        }
+    }
+}
+
+/// Whether `path` names a shared object such as `libc.so.6`
+fn is_shared_object(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(".so") || name.contains(".so."))
+}
+
+/// Find the systemd service a process belongs to from `/proc/<pid>/cgroup`
+///
+/// The innermost `.service` in the cgroup path wins, so a service started
+/// by a user manager is reported instead of the `user@.service` itself.
+fn parse_unit(cgroup: &str) -> Option<String> {
+    cgroup.lines().find_map(|line| {
+        let path = line.splitn(3, ':').nth(2)?;
+        path.rsplit('/').find(|part| part.ends_with(".service")).map(str::to_string)
+    })
+}
+
+impl LsofData {
+    /// Stat `path` as seen from the process's root directory
+    ///
+    /// Returns `None` if the path no longer exists.
+    fn stat_in_root(process: &ProcessHandle, path: &Path) -> io::Result<Option<((u32, u32), u64)>> {
+        match process.stat(&format!("root{}", path.display())) {
+            Ok(stat) => Ok(Some((mountinfo::dev_major_minor(stat.dev), stat.ino))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Check the executable of a process against the file now at its path
+    fn stale_exe(process: &ProcessHandle, pid: &str, diagnostics: &mut Diagnostics) -> Result<Option<StaleFile>> {
+        let link = match process.read_link("exe") {
+            Ok(link) => link.to_string_lossy().into_owned(),
+            // Kernel threads have no executable
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Self::pid_error(pid, err)),
+        };
+        let running = process.stat("exe").map_err(|err| Self::pid_error(pid, err))?;
+        let device = mountinfo::dev_major_minor(running.dev);
+
+        let (path, reason) = match link.strip_suffix(crate::maps::DELETED_SUFFIX) {
+            Some(path) => (PathBuf::from(path), Some(StaleReason::Deleted)),
+            None => {
+                let path = PathBuf::from(link);
+                let reason = match Self::stat_in_root(process, &path) {
+                    Ok(Some(on_disk)) => (on_disk != (device, running.ino)).then_some(StaleReason::Replaced),
+                    Ok(None) => Some(StaleReason::Deleted),
+                    Err(err) => {
+                        diagnostics.unreadable(format!("/proc/{}/root{}", pid, path.display()), &err);
+                        None
+                    }
+                };
+                (path, reason)
+            }
+        };
+        Ok(reason.map(|reason| StaleFile { path, device, inode: running.ino, reason }))
+    }
+
+    /// Check the shared libraries mapped by a process against the files now at their paths
+    ///
+    /// Only inodes are compared: on stacked filesystems such as overlayfs
+    /// the maps report the device of the underlying layer, not the one a
+    /// stat of the path returns.
+    fn stale_libraries(
+        process: &ProcessHandle,
+        pid: &str,
+        entries: &[MapEntry],
+        diagnostics: &mut Diagnostics,
+    ) -> Vec<StaleFile> {
+        let mut seen = HashSet::new();
+        let mut stale = Vec::new();
+
+        for entry in entries {
+            let Some(path) = entry.file_path() else {
+                continue;
+            };
+            if !is_shared_object(path) || !seen.insert((path, entry.device, entry.inode)) {
+                continue;
+            }
+
+            let reason = if entry.is_deleted() {
+                Some(StaleReason::Deleted)
+            } else {
+                match Self::stat_in_root(process, path) {
+                    Ok(Some((_, inode))) => (inode != entry.inode).then_some(StaleReason::Replaced),
+                    Ok(None) => Some(StaleReason::Deleted),
+                    Err(err) => {
+                        diagnostics.unreadable(format!("/proc/{}/root{}", pid, path.display()), &err);
+                        None
+                    }
+                }
+            };
+            if let Some(reason) = reason {
+                stale.push(StaleFile {
+                    path: path.to_path_buf(),
+                    device: entry.device,
+                    inode: entry.inode,
+                    reason,
+                });
+            }
+        }
+        stale.sort_by(|a, b| a.path.cmp(&b.path));
+        stale
+    }
+
+    /// Find processes that still run an executable or map a shared library
+    /// that was deleted or replaced on disk, as after a package upgrade
+    ///
+    /// Paths are resolved through `/proc/<pid>/root`, so processes in
+    /// containers are compared against their own filesystem.
+    pub fn stale_ls(&mut self) -> Result<StaleReport> {
+        let processes = self.visit_processes(|data, pid, process, diagnostics| {
+            let entries = data.get_maps(process).map_err(|err| Self::pid_error(pid, err))?;
+            let exe = Self::stale_exe(process, pid, diagnostics)?;
+            let libraries = Self::stale_libraries(process, pid, &entries, diagnostics);
+            if exe.is_none() && libraries.is_empty() {
+                return Ok(None);
+            }
+
+            Ok(Some(StaleProcess {
+                pid: pid.to_string(),
+                name: data.get_pid_info(process, pid)?.remove("Name"),
+                exe,
+                libraries,
+                unit: process.read_to_string("cgroup").ok().as_deref().and_then(parse_unit),
+            }))
+        })?;
+        Ok(StaleReport::new(processes.into_iter().flatten().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unit_and_grouping() {
+        assert_eq!(parse_unit("0::/system.slice/nginx.service\n"), Some("nginx.service".to_string()));
+        assert_eq!(
+            parse_unit("0::/user.slice/user-1000.slice/user@1000.service/app.slice/foo.service"),
+            Some("foo.service".to_string())
+        );
+        assert_eq!(
+            parse_unit("12:cpu:/\n1:name=systemd:/system.slice/sshd.service"),
+            Some("sshd.service".to_string())
+        );
+        assert_eq!(parse_unit("0::/user.slice/user-1000.slice/session-2.scope"), None);
+
+        assert!(is_shared_object(Path::new("/usr/lib/libssl.so.3")));
+        assert!(is_shared_object(Path::new("/lib64/ld-linux-x86-64.so.2")));
+        assert!(!is_shared_object(Path::new("/usr/lib/locale/locale-archive")));
+
+        let library = |path: &str| StaleFile {
+            path: PathBuf::from(path),
+            device: (8, 1),
+            inode: 1,
+            reason: StaleReason::Deleted,
+        };
+        let process = |pid: &str, unit: &str, libraries| StaleProcess {
+            pid: pid.to_string(),
+            name: None,
+            exe: None,
+            libraries,
+            unit: Some(unit.to_string()),
+        };
+        let report = StaleReport::new(vec![
+            process("20", "b.service", vec![library("/lib/libc.so.6")]),
+            process("3", "a.service", vec![library("/lib/libc.so.6"), library("/lib/libz.so.1")]),
+            process("4", "a.service", vec![]),
+        ]);
+        assert_eq!(report.processes[0].pid, "3");
+        assert_eq!(report.libraries.len(), 2);
+        assert_eq!(report.libraries[0].pids, ["3", "20"]);
+        assert_eq!(report.units, ["a.service", "b.service"]);
+    }
+
+    #[test]
+    fn test_stale_ls() {
+        let mut d = LsofData::new();
+        let report = d.stale_ls().unwrap();
+        // The test binary is freshly built, so it must not be reported as replaced
+        let me = std::process::id().to_string();
+        if let Some(process) = report.processes.iter().find(|p| p.pid == me) {
+            assert!(process.exe.is_none());
+        }
+        assert!(d.scanned_at().is_some());
+    }
+}
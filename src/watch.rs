@@ -0,0 +1,149 @@
+use crate::{Error, Fdinfo, LsofData, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// What to look up when following holders over time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Processes that have a file open or mapped
+    File(PathBuf),
+    /// Processes holding a socket bound to a port
+    Port(String),
+}
+
+/// A change in the set of processes holding a watched target
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    /// A process started holding the target
+    Acquired(Fdinfo),
+    /// A process stopped holding the target, or exited
+    Released(Fdinfo),
+}
+
+/// Result of waiting for the holders of a target to change
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaitOutcome {
+    /// The condition was met; carries the holders seen by the last poll
+    Satisfied(Vec<Fdinfo>),
+    /// The timeout expired first; carries the holders that remained
+    TimedOut(Vec<Fdinfo>),
+}
+
+/// Holders keyed by pid and start time, so a reused pid counts as a new process
+pub(crate) type Holders = HashMap<(String, Option<u64>), Fdinfo>;
+
+impl LsofData {
+    /// Get the processes currently matching a query
+    ///
+    /// A file that does not exist has no holders, so removing a lock file
+    /// counts as releasing it.
+    pub(crate) fn holders(&mut self, query: &Query) -> Result<Holders> {
+        let found = match query {
+            Query::File(path) => match self.target_file_ls(path.to_string_lossy().to_string()) {
+                Err(Error::TargetNotFound(_)) => Ok(Vec::new()),
+                found => found,
+            },
+            Query::Port(port) => self.port_ls(port.clone()),
+        }?;
+        Ok(found
+            .into_iter()
+            .map(|info| ((info.pid.clone(), info.start_time), info))
+            .collect())
+    }
+}
+
+/// Poll delays that double from 25ms up to 1s, bounded by a deadline
+pub(crate) struct Backoff {
+    /// Delay before the next poll
+    delay: Duration,
+    /// Time after which no further poll is made
+    deadline: Instant,
+}
+
+impl Backoff {
+    /// Start a backoff that gives up after `timeout`
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            delay: Duration::from_millis(25),
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    /// Delay before the next poll, or `None` once the deadline has passed
+    pub(crate) fn next_delay(&mut self) -> Option<Duration> {
+        let remaining = self.deadline.checked_duration_since(Instant::now())?;
+        if remaining.is_zero() {
+            return None;
+        }
+        let delay = self.delay.min(remaining);
+        self.delay = (self.delay * 2).min(Duration::from_secs(1));
+        Some(delay)
+    }
+}
+
+/// Sort holders by pid for stable output
+pub(crate) fn sorted(holders: Holders) -> Vec<Fdinfo> {
+    let mut list: Vec<Fdinfo> = holders.into_values().collect();
+    list.sort_by_key(|info| info.pid.parse::<u64>().unwrap_or(u64::MAX));
+    list
+}
+
+/// Events that turn the `before` holders into the `after` holders
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+pub(crate) fn changes(before: &Holders, after: &Holders) -> Vec<WatchEvent> {
+    let mut events: Vec<WatchEvent> = before
+        .iter()
+        .filter(|(key, _)| !after.contains_key(*key))
+        .map(|(_, info)| WatchEvent::Released(info.clone()))
+        .collect();
+    events.extend(
+        after
+            .iter()
+            .filter(|(key, _)| !before.contains_key(*key))
+            .map(|(_, info)| WatchEvent::Acquired(info.clone())),
+    );
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holders(list: &[(&str, u64)]) -> Holders {
+        list.iter()
+            .map(|(pid, start)| {
+                let info = Fdinfo {
+                    pid: pid.to_string(),
+                    start_time: Some(*start),
+                    ..Default::default()
+                };
+                ((info.pid.clone(), info.start_time), info)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(60));
+        let delays: Vec<Duration> = (0..8).filter_map(|_| backoff.next_delay()).collect();
+        assert_eq!(delays[0], Duration::from_millis(25));
+        assert_eq!(delays[1], Duration::from_millis(50));
+        assert_eq!(delays[7], Duration::from_secs(1));
+
+        let mut expired = Backoff::new(Duration::ZERO);
+        assert_eq!(expired.next_delay(), None);
+    }
+
+    #[test]
+    fn test_changes() {
+        let before = holders(&[("1", 10), ("2", 20)]);
+        let after = holders(&[("1", 10), ("2", 21)]);
+
+        let events = changes(&before, &after);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], WatchEvent::Released(info) if info.start_time == Some(20)));
+        assert!(matches!(&events[1], WatchEvent::Acquired(info) if info.start_time == Some(21)));
+        assert!(changes(&after, &after).is_empty());
+    }
+}